@@ -0,0 +1,233 @@
+use std::convert::Infallible;
+use std::io;
+use std::path::PathBuf;
+
+use anyhow::{Context, Result, bail};
+
+use crate::compositor::{self, Backend, Process};
+use crate::config_management::{Config, SortMode};
+use crate::daemon;
+use crate::output::{self, OutputFormat};
+use crate::search;
+use crate::ui::modes;
+
+const HELP: &str = "\
+whereami - window switcher for Hyprland, Niri, Sway and other wlroots compositors
+
+USAGE:
+    whereami [OPTIONS] [COMMAND]
+
+COMMANDS:
+    (none)                  open the switcher, or toggle the one already running
+    show | hide | toggle    control the switcher that is already running
+    next | previous         move its selection, showing it first if it is hidden
+    list                    print every window, ordered by --sort and filtered by --query
+    focus <ID>              focus the window with this id (see `list`)
+    close <ID>              close the window with this id
+
+OPTIONS:
+    --daemon                stay in the background, hidden until `whereami show`
+    --dmenu                 pick one of the lines on stdin and print it, like dmenu
+    --hold                  Alt+Tab style: Tab/Shift+Tab move while the modifier is held,
+                            letting go of it focuses the selected window
+    -p, --prompt <TEXT>     placeholder for the search bar
+    -c, --config <PATH>     use this config instead of ~/.config/whereami/config.toml
+    -b, --backend <NAME>    hyprland, niri, sway or wlr instead of detecting it
+    -m, --mode <MODE>       start in windows, workspaces or apps
+    -q, --query <TEXT>      start with this already typed into the search bar
+    -s, --sort <MODE>       workspace or mru
+    -f, --format <FORMAT>   how `list` prints: plain, json, tsv or a template
+                            like '{id} {class} {title}'
+    --width <PX>            window width
+    --height <PX>           window height
+    -h, --help              print this
+    -V, --version           print the version
+";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Command {
+    /// open the switcher
+    Run,
+    Control(daemon::Command),
+    List,
+    Focus(u64),
+    Close(u64),
+}
+
+/// Everything given on the command line. The options override the config file
+#[derive(Debug)]
+pub struct Cli {
+    pub command: Command,
+    pub daemon: bool,
+    pub dmenu: bool,
+    /// started from a modifier chord that is still held, see `AppState::hold`
+    pub hold: bool,
+    pub prompt: Option<String>,
+    pub config_path: Option<PathBuf>,
+    pub backend: Option<Backend>,
+    pub mode: Option<String>,
+    pub query: Option<String>,
+    pub sort: Option<SortMode>,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub format: OutputFormat,
+}
+
+impl Cli {
+    /// Reads the arguments. `--help` and `--version` are printed here and exit straight away
+    pub fn parse() -> Result<Self> {
+        let mut args = pico_args::Arguments::from_env();
+        if args.contains(["-h", "--help"]) {
+            print!("{HELP}");
+            std::process::exit(0);
+        }
+        if args.contains(["-V", "--version"]) {
+            println!("whereami {}", env!("CARGO_PKG_VERSION"));
+            std::process::exit(0);
+        }
+
+        let daemon = args.contains("--daemon");
+        let dmenu = args.contains("--dmenu");
+        let hold = args.contains("--hold");
+        let prompt = args.opt_value_from_str(["-p", "--prompt"])?;
+        let config_path = args.opt_value_from_os_str(["-c", "--config"], |path| {
+            Ok::<_, Infallible>(PathBuf::from(path))
+        })?;
+        let backend = args.opt_value_from_str(["-b", "--backend"])?;
+        let mode: Option<String> = args.opt_value_from_str(["-m", "--mode"])?;
+        if let Some(mode) = &mode
+            && !modes::all().iter().any(|m| m.name() == mode)
+        {
+            bail!("unknown mode {mode:?}, expected windows, workspaces or apps");
+        }
+        let query = args.opt_value_from_str(["-q", "--query"])?;
+        let sort = args.opt_value_from_str(["-s", "--sort"])?;
+        let width = args.opt_value_from_str("--width")?;
+        let height = args.opt_value_from_str("--height")?;
+        let format = args
+            .opt_value_from_str(["-f", "--format"])?
+            .unwrap_or_default();
+
+        // options are taken out first, so the command can be anywhere
+        let command = match args.opt_free_from_str::<String>()?.as_deref() {
+            None => Command::Run,
+            Some("list") => Command::List,
+            Some("focus") => Command::Focus(
+                args.free_from_str()
+                    .context("focus needs the id of a window")?,
+            ),
+            Some("close") => Command::Close(
+                args.free_from_str()
+                    .context("close needs the id of a window")?,
+            ),
+            Some(other) => match daemon::Command::parse(other) {
+                Some(command) => Command::Control(command),
+                None => bail!("unknown command {other:?}, see whereami --help"),
+            },
+        };
+        if dmenu && (daemon || mode.is_some() || command != Command::Run) {
+            bail!("--dmenu can't be combined with --daemon, --mode or a command");
+        }
+        let rest = args.finish();
+        if !rest.is_empty() {
+            bail!("unexpected arguments: {rest:?}, see whereami --help");
+        }
+
+        Ok(Cli {
+            command,
+            daemon,
+            dmenu,
+            hold,
+            prompt,
+            config_path,
+            backend,
+            mode,
+            query,
+            sort,
+            width,
+            height,
+            format,
+        })
+    }
+
+    /// The config file with the command line overrides on top
+    pub fn load_config(&self) -> io::Result<Config> {
+        let mut config = Config::new(self.config_path.as_deref())?;
+        if let Some(sort) = self.sort {
+            config.behavior.sort = sort;
+        }
+        if let Some(width) = self.width {
+            config.window.width = width;
+        }
+        if let Some(height) = self.height {
+            config.window.height = height;
+        }
+        Ok(config)
+    }
+
+    /// What a running instance is told to use along with the command
+    pub fn overrides(&self) -> daemon::Overrides {
+        daemon::Overrides {
+            mode: self.mode.clone(),
+            query: self.query.clone(),
+            sort: self.sort,
+        }
+    }
+
+    /// The running instance already has its surface and config, so these can't reach it
+    pub fn warn_unforwarded(&self) {
+        let ignored: Vec<&str> = [
+            ("--prompt", self.prompt.is_some()),
+            ("--width", self.width.is_some()),
+            ("--height", self.height.is_some()),
+            ("--backend", self.backend.is_some()),
+            ("--config", self.config_path.is_some()),
+        ]
+        .into_iter()
+        .filter_map(|(flag, given)| given.then_some(flag))
+        .collect();
+        if !ignored.is_empty() {
+            eprintln!(
+                "whereami is already running, ignoring {}. Restart it to use them",
+                ignored.join(", ")
+            );
+        }
+    }
+
+    /// Prints `windows` in `format` and sorted like the switcher would, leaving out the
+    /// ones `query` does not match (which then come best match first)
+    fn list(&self, mut windows: Vec<Process>) -> Result<()> {
+        let config = self.load_config()?;
+        compositor::sort_windows(&mut windows, config.behavior.sort);
+        if let Some(query) = &self.query {
+            let (matches, error) = search::search(&windows, query, &config.behavior.search_fields);
+            if let Some(error) = error {
+                bail!("invalid query: {error}");
+            }
+            windows = matches.into_iter().map(|m| m.item).collect();
+        }
+        print!("{}", output::format_windows(&windows, &self.format)?);
+        Ok(())
+    }
+
+    /// `list`, `focus` and `close`, which only need the compositor and no window
+    pub fn run_headless(&self) -> Result<()> {
+        let compositor = compositor::connect(self.backend)?;
+        let windows = compositor.get_windows()?;
+        let (Command::Focus(id) | Command::Close(id)) = self.command else {
+            return self.list(windows);
+        };
+
+        let window = windows
+            .into_iter()
+            .find(|window| window.window_id == Some(id))
+            .with_context(|| format!("no window with id {id}"))?;
+        // the compositors' IPC is async, and Hyprland's needs tokio underneath
+        let executor = iced::executor::Default::new()?;
+        if self.command == Command::Focus(id) {
+            executor.block_on(compositor.focus_window(window))
+        } else {
+            executor.block_on(compositor.close_window(window))
+        }
+    }
+}
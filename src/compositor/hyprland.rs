@@ -0,0 +1,346 @@
+use anyhow::{Context, Result};
+use hyprland::{
+    data::{Client, Clients, Monitor, Monitors, Workspaces},
+    dispatch::{DispatchType, WindowIdentifier, WorkspaceIdentifierWithSpecial},
+    event_listener::{Event, EventStream},
+    shared::{Address, HyprData, HyprDataActiveOptional, HyprDataVec},
+};
+use iced::futures::{
+    StreamExt,
+    stream::{self, BoxStream},
+};
+
+use super::{Capability, Compositor, FullscreenStatus, Process, WindowEvent, Workspace};
+
+pub struct HyprlandCompositor;
+
+/// Hyprland addresses are hex strings like `0x55d3c1a0b2c0`, so they fit in `Process::window_id`
+fn address_to_id(address: &Address) -> Option<u64> {
+    u64::from_str_radix(address.to_string().trim_start_matches("0x"), 16).ok()
+}
+
+fn id_to_address(id: u64) -> Address {
+    Address::new(format!("{id:x}"))
+}
+
+/// What `togglespecialworkspace` takes for a special workspace called `name`.
+/// The unnamed scratchpad is called "special:special" but is toggled without a name
+fn special_workspace_arg(name: &str) -> Option<String> {
+    name.strip_prefix("special:")
+        .filter(|name| *name != "special")
+        .map(String::from)
+}
+
+/// `monitors` is used to turn the client's monitor id into its name
+fn hyprland_process(cl: &Client, monitors: &[Monitor]) -> Process {
+    let fs_mode = match cl.fullscreen {
+        hyprland::data::FullscreenMode::Fullscreen => FullscreenStatus::Fullscreen,
+        hyprland::data::FullscreenMode::Maximized => FullscreenStatus::Maximised,
+        _ => FullscreenStatus::None,
+    };
+    let workspace_id = u64::from(cl.workspace.id.unsigned_abs());
+    // focusHistoryID counts up from 0 (the focused window), and is -1 if it was never focused
+    let focus_stamp = u64::try_from(cl.focus_history_id).map_or(0, |id| u64::from(u8::MAX) - id);
+    Process {
+        pid: cl.pid,
+        title: cl.title.clone(),
+        // some apps only set their class once mapped
+        class: if cl.class.is_empty() {
+            cl.initial_class.clone()
+        } else {
+            cl.class.clone()
+        },
+        window_id: address_to_id(&cl.address),
+        workspace: workspace_id,
+        workspace_name: cl.workspace.name.clone(),
+        monitor: monitors
+            .iter()
+            .find(|m| Some(m.id) == cl.monitor)
+            .map(|m| m.name.clone())
+            .unwrap_or_default(),
+        fullscreen: fs_mode,
+        floating: cl.floating,
+        focused: cl.focus_history_id == 0,
+        focus_stamp,
+    }
+}
+
+/// Looks a single client up again so events that only carry an address can send a full `Process`
+async fn hyprland_changed(address: &Address) -> Result<Option<WindowEvent>> {
+    let client = Clients::get_async()
+        .await
+        .context("Could not get clients")?
+        .into_iter()
+        .find(|cl| cl.address == *address && cl.title != "whereami");
+    let Some(client) = client else {
+        return Ok(None);
+    };
+    let monitors = Monitors::get_async()
+        .await
+        .context("Could not get monitors")?
+        .to_vec();
+    Ok(Some(WindowEvent::Changed(hyprland_process(
+        &client, &monitors,
+    ))))
+}
+
+/// Turns one `.socket2.sock` event into the matching `WindowEvent`, if it is one we care about
+async fn hyprland_event(event: Event) -> Result<Option<WindowEvent>> {
+    match event {
+        Event::WindowOpened(ev) => hyprland_changed(&ev.window_address).await,
+        Event::WindowMoved(ev) => hyprland_changed(&ev.window_address).await,
+        Event::FloatStateChanged(ev) => hyprland_changed(&ev.address).await,
+        // this one does not say which window, but it is always the active one
+        Event::FullscreenStateChanged(_) => {
+            let active = Client::get_active_async()
+                .await
+                .context("Could not get active window")?;
+            match active {
+                Some(cl) if cl.title != "whereami" => hyprland_changed(&cl.address).await,
+                _ => Ok(None),
+            }
+        }
+        Event::WindowClosed(address) => Ok(address_to_id(&address).map(WindowEvent::Closed)),
+        Event::WindowTitleChanged(ev) => Ok(address_to_id(&ev.address)
+            .filter(|_| ev.title != "whereami")
+            .map(|window_id| WindowEvent::TitleChanged {
+                window_id,
+                title: ev.title,
+            })),
+        Event::ActiveWindowChanged(data) => Ok(Some(WindowEvent::Focused(
+            data.and_then(|d| address_to_id(&d.address)),
+        ))),
+        _ => Ok(None),
+    }
+}
+
+#[async_trait::async_trait]
+impl Compositor for HyprlandCompositor {
+    fn get_windows(&self) -> Result<Vec<Process>> {
+        let mut clients = hyprland::data::Clients::get()
+            .context("Could not get clients")?
+            .to_vec();
+
+        clients.retain(|client| client.title != "whereami");
+        clients.sort_by_key(|client| client.workspace.id);
+
+        let monitors = Monitors::get().context("Could not get monitors")?.to_vec();
+        let processes = clients
+            .iter()
+            .map(|cl| hyprland_process(cl, &monitors))
+            .collect::<Vec<Process>>();
+
+        Ok(processes)
+    }
+
+    fn can(&self, _capability: Capability) -> bool {
+        true
+    }
+
+    fn get_workspaces(&self) -> Result<Vec<Workspace>> {
+        let mut workspaces = Workspaces::get()
+            .context("Could not get workspaces")?
+            .to_vec();
+        // special workspaces have negative ids, they go after the normal ones
+        workspaces.sort_by_key(|ws| (ws.id < 0, ws.id.unsigned_abs()));
+        let windows = self.get_windows()?;
+        Ok(workspaces
+            .into_iter()
+            .map(|ws| {
+                let id = u64::from(ws.id.unsigned_abs());
+                Workspace {
+                    id,
+                    number: id,
+                    windows: windows
+                        .iter()
+                        .filter(|w| w.workspace == id)
+                        .cloned()
+                        .collect(),
+                    name: ws.name,
+                    monitor: ws.monitor,
+                }
+            })
+            .collect())
+    }
+
+    fn window_events(&self) -> BoxStream<'static, Result<WindowEvent>> {
+        EventStream::new()
+            .then(|event| async move {
+                match event {
+                    Ok(event) => hyprland_event(event).await.transpose(),
+                    Err(e) => Some(Err(
+                        anyhow::Error::new(e).context("Hyprland event socket failed")
+                    )),
+                }
+            })
+            .filter_map(|event| async move { event })
+            .chain(stream::once(async {
+                Err(anyhow::anyhow!("Hyprland event socket closed"))
+            }))
+            .boxed()
+    }
+
+    async fn focus_window(&self, process: Process) -> Result<()> {
+        let Some(id) = process.window_id else {
+            // without an address the best we can do is go to the workspace
+            hyprland::dispatch::Dispatch::call_async(DispatchType::Workspace(
+                WorkspaceIdentifierWithSpecial::Id(i32::try_from(process.workspace).unwrap_or(1)),
+            ))
+            .await
+            .context(format!(
+                "Could not switch to workspace {}",
+                process.workspace
+            ))?;
+            return Ok(());
+        };
+        let address = id_to_address(id);
+
+        // the list may be stale by now, so look the window up again
+        let client = Clients::get_async()
+            .await
+            .context("Could not get clients")?
+            .into_iter()
+            .find(|cl| cl.address == address)
+            .context(format!("Window {address} no longer exists"))?;
+
+        // special workspaces (scratchpads) have negative ids and are hidden unless toggled on,
+        // focusing a window inside a hidden one does nothing
+        if client.workspace.id < 0 {
+            let monitors = Monitors::get_async()
+                .await
+                .context("Could not get monitors")?;
+            let is_visible = monitors
+                .iter()
+                .any(|m| m.special_workspace.id == client.workspace.id);
+            if !is_visible {
+                hyprland::dispatch::Dispatch::call_async(DispatchType::ToggleSpecialWorkspace(
+                    special_workspace_arg(&client.workspace.name),
+                ))
+                .await
+                .context(format!(
+                    "Could not show special workspace {}",
+                    client.workspace.name
+                ))?;
+            }
+        }
+
+        hyprland::dispatch::Dispatch::call_async(DispatchType::FocusWindow(
+            WindowIdentifier::Address(address.clone()),
+        ))
+        .await
+        .context(format!("Could not focus window {address}"))?;
+
+        // a window in a group may be a hidden tab, so make it the active one (1-indexed)
+        if client.grouped.len() > 1
+            && let Some(pos) = client.grouped.iter().position(|a| **a == address)
+        {
+            hyprland::dispatch::Dispatch::call_async(DispatchType::Custom(
+                "changegroupactive",
+                &(pos + 1).to_string(),
+            ))
+            .await
+            .context(format!("Could not switch group to window {address}"))?;
+        }
+        Ok(())
+    }
+
+    async fn close_window(&self, process: Process) -> Result<()> {
+        // by pid Hyprland closes whichever window of the process it finds first
+        let id = process.window_id.context("window is missing its address")?;
+        hyprland::dispatch::Dispatch::call_async(DispatchType::CloseWindow(
+            WindowIdentifier::Address(id_to_address(id)),
+        ))
+        .await
+        .context(format!("Could not close {}", process.title))?;
+        Ok(())
+    }
+
+    async fn focus_workspace(&self, workspace: Workspace) -> Result<()> {
+        let target = if workspace.name.starts_with("special:") {
+            DispatchType::ToggleSpecialWorkspace(special_workspace_arg(&workspace.name))
+        } else {
+            DispatchType::Workspace(WorkspaceIdentifierWithSpecial::Id(
+                i32::try_from(workspace.id).context("workspace id out of range")?,
+            ))
+        };
+        hyprland::dispatch::Dispatch::call_async(target)
+            .await
+            .context(format!(
+                "Could not switch to workspace {}",
+                workspace.label()
+            ))?;
+        Ok(())
+    }
+
+    async fn move_to_workspace(&self, process: Process, target: String) -> Result<()> {
+        let id = process.window_id.context("window is missing its address")?;
+        let workspace = match target.parse::<i32>() {
+            Ok(number) => WorkspaceIdentifierWithSpecial::Id(number),
+            Err(_) => WorkspaceIdentifierWithSpecial::Name(&target),
+        };
+        hyprland::dispatch::Dispatch::call_async(DispatchType::MoveToWorkspaceSilent(
+            workspace,
+            Some(WindowIdentifier::Address(id_to_address(id))),
+        ))
+        .await
+        .context(format!(
+            "Could not move {} to workspace {target}",
+            process.title
+        ))?;
+        Ok(())
+    }
+
+    async fn toggle_floating(&self, process: Process) -> Result<()> {
+        let id = process.window_id.context("window is missing its address")?;
+        hyprland::dispatch::Dispatch::call_async(DispatchType::ToggleFloating(Some(
+            WindowIdentifier::Address(id_to_address(id)),
+        )))
+        .await
+        .context(format!("Could not float {}", process.title))?;
+        Ok(())
+    }
+
+    async fn toggle_fullscreen(&self, process: Process) -> Result<()> {
+        // fullscreen only works on the active window
+        let title = process.title.clone();
+        self.focus_window(process).await?;
+        hyprland::dispatch::Dispatch::call_async(DispatchType::ToggleFullscreen(
+            hyprland::dispatch::FullscreenType::Real,
+        ))
+        .await
+        .context(format!("Could not make {title} fullscreen"))?;
+        Ok(())
+    }
+
+    async fn toggle_pin(&self, process: Process) -> Result<()> {
+        let id = process.window_id.context("window is missing its address")?;
+        hyprland::dispatch::Dispatch::call_async(DispatchType::TogglePinWindow(
+            WindowIdentifier::Address(id_to_address(id)),
+        ))
+        .await
+        // Hyprland only pins floating windows
+        .context(format!("Could not pin {}, is it floating?", process.title))?;
+        Ok(())
+    }
+
+    async fn move_to_monitor(&self, process: Process, monitor: String) -> Result<()> {
+        // `movewindow mon:` only moves the active window, going through the monitor's
+        // workspace moves any of them without changing focus
+        let workspace = Monitors::get_async()
+            .await
+            .context("Could not get monitors")?
+            .into_iter()
+            .find(|m| m.name == monitor)
+            .context(format!("There is no monitor {monitor}"))?
+            .active_workspace
+            .id;
+        self.move_to_workspace(process, workspace.to_string()).await
+    }
+
+    async fn launch(&self, command: String) -> Result<()> {
+        hyprland::dispatch::Dispatch::call_async(DispatchType::Exec(&command))
+            .await
+            .context(format!("Could not launch {command}"))?;
+        Ok(())
+    }
+}
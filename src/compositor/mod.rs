@@ -0,0 +1,360 @@
+use std::collections::BTreeMap;
+use std::process::Stdio;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use anyhow::{Context, Result, anyhow};
+use iced::futures::StreamExt;
+use iced::futures::stream::{self, BoxStream};
+use serde::Serialize;
+
+use crate::config_management::SortMode;
+
+mod hyprland;
+mod niri;
+mod sway;
+mod wlr;
+
+pub use hyprland::HyprlandCompositor;
+pub use niri::NiriCompositor;
+pub use sway::SwayCompositor;
+pub use wlr::WlrCompositor;
+
+/// All the information needed from the compositors' windows
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Process {
+    pub pid: i32,
+    pub title: String,
+    /// Hyprland/X11 class, or the Wayland `app_id`
+    pub class: String,
+    pub window_id: Option<u64>,
+    pub workspace: u64,
+    /// empty if the compositor does not name workspaces
+    pub workspace_name: String,
+    /// the output the window is on, empty if unknown
+    pub monitor: String,
+    pub fullscreen: FullscreenStatus,
+    pub floating: bool,
+    pub focused: bool,
+    /// bigger means focused more recently. Only comparable between windows of the same compositor
+    pub focus_stamp: u64,
+}
+
+impl Process {
+    /// Short description of the window state, as shown in the list
+    pub fn status(&self) -> &'static str {
+        match self.fullscreen {
+            FullscreenStatus::Fullscreen => "Fullscreen",
+            FullscreenStatus::Maximised => "Maximised",
+            FullscreenStatus::None => {
+                if self.floating {
+                    "Float"
+                } else {
+                    "Tiled"
+                }
+            }
+        }
+    }
+}
+
+/// A workspace and the windows on it
+#[derive(Debug, Clone, Default)]
+pub struct Workspace {
+    /// what the compositor knows the workspace by
+    pub id: u64,
+    /// the number its windows have as `Process::workspace`, which is also what is shown.
+    /// Not always the id: niri's ids keep growing, so whereami numbers them itself
+    pub number: u64,
+    /// empty if the compositor does not name workspaces
+    pub name: String,
+    pub monitor: String,
+    pub windows: Vec<Process>,
+}
+
+impl Workspace {
+    /// The name, or the number for unnamed workspaces
+    pub fn label(&self) -> String {
+        if self.name.is_empty() {
+            self.number.to_string()
+        } else {
+            self.name.clone()
+        }
+    }
+}
+
+/// Orders `windows` the way `sort` says, for the list and `whereami list`
+pub fn sort_windows(windows: &mut [Process], sort: SortMode) {
+    match sort {
+        SortMode::Workspace => windows.sort_by_key(|c| c.workspace),
+        SortMode::Mru => {
+            windows.sort_by_key(|c| std::cmp::Reverse(c.focus_stamp));
+            // the window you are in goes second so Enter toggles between the last two
+            if windows.len() > 1 && windows[0].focused {
+                windows.swap(0, 1);
+            }
+        }
+    }
+}
+
+/// Puts `windows` on the workspaces they say they are on, for compositors that can't
+/// list workspaces themselves. Empty workspaces are missed
+pub fn group_by_workspace(windows: Vec<Process>) -> Vec<Workspace> {
+    let mut workspaces: BTreeMap<u64, Workspace> = BTreeMap::new();
+    for window in windows {
+        workspaces
+            .entry(window.workspace)
+            .or_insert_with(|| Workspace {
+                id: window.workspace,
+                number: window.workspace,
+                name: window.workspace_name.clone(),
+                monitor: window.monitor.clone(),
+                windows: Vec::new(),
+            })
+            .windows
+            .push(window);
+    }
+    workspaces.into_values().collect()
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FullscreenStatus {
+    Fullscreen,
+    Maximised,
+
+    #[default]
+    None,
+}
+
+/// A change to the window list, pushed by the compositor as it happens
+#[derive(Debug, Clone)]
+pub enum WindowEvent {
+    /// A window was opened, or something about it (workspace, state...) changed
+    Changed(Process),
+    TitleChanged {
+        window_id: u64,
+        title: String,
+    },
+    Closed(u64),
+    Focused(Option<u64>),
+    /// The whole list has to be replaced, e.g. when niri renumbers the workspaces
+    Reset(Vec<Process>),
+}
+
+/// What a compositor can do to a window besides focusing and closing it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Capability {
+    MoveToWorkspace,
+    Float,
+    Fullscreen,
+    Pin,
+    MoveToMonitor,
+}
+
+#[async_trait::async_trait]
+pub(crate) trait Compositor {
+    fn get_windows(&self) -> Result<Vec<Process>>;
+
+    /// Whether the method for `capability` does anything here rather than failing.
+    /// The action menu leaves out what can't be done
+    fn can(&self, _capability: Capability) -> bool {
+        false
+    }
+
+    /// Stream of changes to the window list. Ends (with an error) when the compositor
+    /// connection drops, at which point the UI falls back to polling `get_windows`
+    fn window_events(&self) -> BoxStream<'static, Result<WindowEvent>>;
+
+    /// Every workspace with the windows on it, empty ones included where the compositor
+    /// says which exist
+    fn get_workspaces(&self) -> Result<Vec<Workspace>> {
+        Ok(group_by_workspace(self.get_windows()?))
+    }
+
+    async fn focus_window(&self, process: Process) -> Result<()>;
+    async fn close_window(&self, process: Process) -> Result<()>;
+
+    /// Moves the window to the workspace with the number or name `target`, without following it
+    async fn move_to_workspace(&self, _process: Process, _target: String) -> Result<()> {
+        anyhow::bail!("this compositor can't move windows between workspaces")
+    }
+
+    async fn toggle_floating(&self, _process: Process) -> Result<()> {
+        anyhow::bail!("this compositor can't float windows")
+    }
+
+    async fn toggle_fullscreen(&self, _process: Process) -> Result<()> {
+        anyhow::bail!("this compositor can't make windows fullscreen")
+    }
+
+    /// Pinned windows show on every workspace
+    async fn toggle_pin(&self, _process: Process) -> Result<()> {
+        anyhow::bail!("this compositor can't pin windows")
+    }
+
+    /// Moves the window to the output called `monitor`
+    async fn move_to_monitor(&self, _process: Process, _monitor: String) -> Result<()> {
+        anyhow::bail!("this compositor can't move windows between monitors")
+    }
+
+    /// Kills the window's process outright, for when closing it politely does nothing
+    async fn kill(&self, process: Process) -> Result<()> {
+        // 0 and below mean the pid is unknown, and `kill 0` would hit our own process group
+        if process.pid <= 0 {
+            anyhow::bail!("Don't know the process of {}", process.title);
+        }
+        let status = std::process::Command::new("kill")
+            .arg("-KILL")
+            .arg(process.pid.to_string())
+            .status()
+            .context("Failed to run kill")?;
+        if !status.success() {
+            anyhow::bail!("Could not kill process {}", process.pid);
+        }
+        Ok(())
+    }
+
+    /// Starts `command` (a shell command line). Compositors that can are asked to do it,
+    /// so the app ends up as their child rather than whereami's
+    async fn launch(&self, command: String) -> Result<()> {
+        // the app outlives whereami, so it must not hang on to its stdio
+        std::process::Command::new("sh")
+            .arg("-c")
+            .arg(&command)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .context(format!("Failed to launch {command}"))?;
+        Ok(())
+    }
+
+    /// Switches to `workspace`. Without a way to do that directly, the window on it
+    /// that was used last is focused, which takes you there too
+    async fn focus_workspace(&self, workspace: Workspace) -> Result<()> {
+        let label = workspace.label();
+        let window = workspace
+            .windows
+            .into_iter()
+            .max_by_key(|w| w.focus_stamp)
+            .context(format!(
+                "Can't switch to workspace {label}, there is no window on it"
+            ))?;
+        self.focus_window(window).await
+    }
+}
+
+/// The compositors whereami can talk to, for picking one by hand
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backend {
+    Hyprland,
+    Niri,
+    Sway,
+    /// wlr/ext foreign toplevel, for everything else
+    Wlr,
+}
+
+impl FromStr for Backend {
+    type Err = anyhow::Error;
+
+    fn from_str(name: &str) -> Result<Self> {
+        match name {
+            "hyprland" => Ok(Backend::Hyprland),
+            "niri" => Ok(Backend::Niri),
+            "sway" => Ok(Backend::Sway),
+            "wlr" => Ok(Backend::Wlr),
+            _ => Err(anyhow!(
+                "unknown backend {name:?}, expected hyprland, niri, sway or wlr"
+            )),
+        }
+    }
+}
+
+/// Stands in for the compositor where none is needed, like `--dmenu`, which only reads
+/// stdin and should work on any desktop
+pub struct NoCompositor;
+
+#[async_trait::async_trait]
+impl Compositor for NoCompositor {
+    fn get_windows(&self) -> Result<Vec<Process>> {
+        Ok(Vec::new())
+    }
+
+    fn window_events(&self) -> BoxStream<'static, Result<WindowEvent>> {
+        stream::pending().boxed()
+    }
+
+    async fn focus_window(&self, _process: Process) -> Result<()> {
+        anyhow::bail!("not connected to a compositor")
+    }
+
+    async fn close_window(&self, _process: Process) -> Result<()> {
+        anyhow::bail!("not connected to a compositor")
+    }
+}
+
+/// Connects to `backend`, or to whichever compositor is running when it is `None`.
+/// Hyprland, Niri and Sway are found through their environment variables,
+/// anything else goes through the wlr/ext foreign toplevel protocols
+pub fn connect(backend: Option<Backend>) -> Result<Arc<dyn Compositor + Send + Sync>> {
+    let backend = backend.unwrap_or_else(|| {
+        if std::env::var("NIRI_SOCKET").is_ok() {
+            Backend::Niri
+        } else if std::env::var("HYPRLAND_INSTANCE_SIGNATURE").is_ok() {
+            Backend::Hyprland
+        } else if std::env::var("SWAYSOCK").is_ok() {
+            Backend::Sway
+        } else {
+            Backend::Wlr
+        }
+    });
+    Ok(match backend {
+        Backend::Hyprland => Arc::new(HyprlandCompositor),
+        Backend::Niri => {
+            Arc::new(NiriCompositor::new().context("Failed to connect to niri socket")?)
+        }
+        Backend::Sway => {
+            Arc::new(SwayCompositor::new().context("Failed to connect to sway socket")?)
+        }
+        Backend::Wlr => Arc::new(WlrCompositor::new().context("No supported compositor found")?),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn window(window_id: u64, focus_stamp: u64, focused: bool) -> Process {
+        Process {
+            window_id: Some(window_id),
+            focus_stamp,
+            focused,
+            ..Default::default()
+        }
+    }
+
+    fn sorted(mut windows: Vec<Process>) -> Vec<u64> {
+        sort_windows(&mut windows, SortMode::Mru);
+        windows.iter().filter_map(|w| w.window_id).collect()
+    }
+
+    #[test]
+    fn mru_puts_the_focused_window_second() {
+        // the one you are in was used last, so the one before it comes first
+        let windows = vec![
+            window(1, 10, false),
+            window(2, 30, true),
+            window(3, 20, false),
+        ];
+        assert_eq!(sorted(windows), [3, 2, 1]);
+        // nothing else to go back to
+        assert_eq!(sorted(vec![window(1, 10, true)]), [1]);
+        // e.g. the focused window is whereami itself, which is not listed
+        let windows = vec![
+            window(1, 10, false),
+            window(2, 30, false),
+            window(3, 20, false),
+        ];
+        assert_eq!(sorted(windows), [2, 3, 1]);
+    }
+}
@@ -0,0 +1,447 @@
+use std::collections::HashMap;
+use std::sync::{Mutex, MutexGuard};
+
+use anyhow::{Context, Result};
+use iced::futures::{StreamExt, channel::mpsc, stream::BoxStream};
+use niri_ipc::{
+    Action, Request, Response, Window, Workspace, WorkspaceReferenceArg, socket::Socket,
+};
+
+use super::{Capability, Compositor, FullscreenStatus, Process, WindowEvent};
+
+pub struct NiriCompositor {
+    pub socket: Mutex<Socket>,
+}
+
+/// Niri's workspace IDs increment infinitely (e.g., closing and opening
+/// workspaces might leave you with IDs like 1, 3, and 6), and each output
+/// counts its own workspaces from 1. To give every workspace one number that
+/// doesn't jump around, they are numbered in the order they are listed in:
+/// by output, then by their place on it.
+///
+/// Example, with workspaces 1 and 2 on DP-1 and 1 on HDMI-A-1:
+/// DP-1 1 -> 1, DP-1 2 -> 2, HDMI-A-1 1 -> 3
+///
+/// Windows, the workspaces mode and `move_to_workspace` all go by this number
+fn niri_workspace_numbers(workspaces: &HashMap<u64, Workspace>) -> HashMap<u64, u64> {
+    let mut listed = workspaces.values().collect::<Vec<_>>();
+    listed.sort_by_key(|ws| (ws.output.clone(), ws.idx));
+    listed
+        .into_iter()
+        .zip(1..) // +1 because programmers count from 0, humans from 1
+        .map(|(ws, number)| (ws.id, number))
+        .collect()
+}
+
+fn niri_process(
+    c: &Window,
+    numbers: &HashMap<u64, u64>,
+    workspaces: &HashMap<u64, Workspace>,
+) -> Option<Process> {
+    if c.title.as_deref() == Some("whereami") {
+        return None;
+    }
+    let fs_mode = match c.layout.window_offset_in_tile {
+        (0.0, 0.0) => FullscreenStatus::Fullscreen,
+        _ => FullscreenStatus::None,
+    };
+    let ws_id = c
+        .workspace_id
+        .and_then(|id| numbers.get(&id))
+        .copied()
+        .unwrap_or(0);
+    let workspace = c.workspace_id.and_then(|id| workspaces.get(&id));
+    let pid = c.pid?;
+    Some(Process {
+        pid,
+        title: c.title.as_deref().unwrap_or("Unknown").to_string(),
+        class: c.app_id.clone().unwrap_or_default(),
+        window_id: Some(c.id),
+        workspace: ws_id,
+        workspace_name: workspace.and_then(|ws| ws.name.clone()).unwrap_or_default(),
+        monitor: workspace
+            .and_then(|ws| ws.output.clone())
+            .unwrap_or_default(),
+        fullscreen: fs_mode,
+        floating: c.is_floating,
+        focused: c.is_focused,
+        focus_stamp: c
+            .focus_timestamp
+            .map_or(0, |t| t.secs * 1_000_000_000 + u64::from(t.nanos)),
+    })
+}
+
+fn niri_processes(windows: &[Window], workspaces: &HashMap<u64, Workspace>) -> Vec<Process> {
+    let numbers = niri_workspace_numbers(workspaces);
+    let mut windows = windows.iter().collect::<Vec<_>>();
+    windows.sort_by_key(|client| client.workspace_id.and_then(|id| numbers.get(&id)));
+    windows
+        .into_iter()
+        .filter_map(|c| niri_process(c, &numbers, workspaces))
+        .collect()
+}
+
+/// Reads niri's event stream until it fails or the receiving end is gone.
+/// niri only sends deltas, so the full window and workspace sets are tracked
+/// here to fill in what `niri_process` needs
+fn niri_event_loop(tx: &mpsc::UnboundedSender<Result<WindowEvent>>) -> Result<()> {
+    let mut socket = Socket::connect().context("failed to connect to niri socket")?;
+    match socket
+        .send(Request::EventStream)
+        .context("Failed to send event stream request")?
+    {
+        Ok(Response::Handled) => {}
+        Ok(_) => anyhow::bail!("unexpected response"),
+        Err(e) => anyhow::bail!("niri returned error {e}"),
+    }
+    let mut read_event = socket.read_events();
+    let mut windows: HashMap<u64, Window> = HashMap::new();
+    let mut workspaces: HashMap<u64, Workspace> = HashMap::new();
+
+    loop {
+        let event = read_event().context("niri event stream closed")?;
+        let changed = match event {
+            // names, outputs and numbers may have changed, which every window shows
+            niri_ipc::Event::WorkspacesChanged { workspaces: new } => {
+                workspaces = new.into_iter().map(|ws| (ws.id, ws)).collect();
+                None
+            }
+            niri_ipc::Event::WindowsChanged { windows: new } => {
+                windows = new.into_iter().map(|w| (w.id, w)).collect();
+                None
+            }
+            niri_ipc::Event::WindowOpenedOrChanged { window } => {
+                let id = window.id;
+                windows.insert(id, window);
+                Some(vec![id])
+            }
+            niri_ipc::Event::WindowClosed { id } => {
+                windows.remove(&id);
+                if tx.unbounded_send(Ok(WindowEvent::Closed(id))).is_err() {
+                    return Ok(());
+                }
+                Some(Vec::new())
+            }
+            // the timestamp is debounced by niri, so this is the one that counts for ordering
+            niri_ipc::Event::WindowFocusTimestampChanged {
+                id,
+                focus_timestamp,
+            } => {
+                if let Some(window) = windows.get_mut(&id) {
+                    window.focus_timestamp = focus_timestamp;
+                }
+                Some(vec![id])
+            }
+            niri_ipc::Event::WindowFocusChanged { id } => {
+                for window in windows.values_mut() {
+                    window.is_focused = Some(window.id) == id;
+                }
+                if tx.unbounded_send(Ok(WindowEvent::Focused(id))).is_err() {
+                    return Ok(());
+                }
+                continue;
+            }
+            niri_ipc::Event::WindowLayoutsChanged { changes } => {
+                let mut ids = Vec::new();
+                for (id, layout) in changes {
+                    if let Some(window) = windows.get_mut(&id) {
+                        window.layout = layout;
+                        ids.push(id);
+                    }
+                }
+                Some(ids)
+            }
+            _ => continue,
+        };
+
+        let events = if let Some(ids) = changed {
+            let numbers = niri_workspace_numbers(&workspaces);
+            ids.iter()
+                .filter_map(|id| windows.get(id))
+                .filter_map(|w| niri_process(w, &numbers, &workspaces))
+                .map(WindowEvent::Changed)
+                .collect()
+        } else {
+            let windows = windows.values().cloned().collect::<Vec<_>>();
+            vec![WindowEvent::Reset(niri_processes(&windows, &workspaces))]
+        };
+        for event in events {
+            if tx.unbounded_send(Ok(event)).is_err() {
+                return Ok(());
+            }
+        }
+    }
+}
+
+impl NiriCompositor {
+    pub fn new() -> Result<Self> {
+        Ok(Self {
+            socket: Mutex::new(Socket::connect().context("failed to connect to niri socket")?),
+        })
+    }
+    fn get_socket(&self) -> Result<MutexGuard<'_, Socket>> {
+        let mut socket = self.socket.lock().unwrap();
+        if socket.send(Request::Version).is_err() {
+            *socket = Socket::connect().context("failed to reconnect to niri socket")?;
+        }
+
+        Ok(socket)
+    }
+
+    /// Sends an action that acts on one window. All of them fall back to the focused
+    /// window when the id is missing, so it has to be there
+    fn window_action(&self, action: Action) -> Result<()> {
+        let mut socket = self.get_socket()?;
+        let reply = socket
+            .send(Request::Action(action))
+            .context("Failed to send window action")?;
+        match reply {
+            Ok(_) => Ok(()),
+            Err(e) => anyhow::bail!("niri returned error: {e}"),
+        }
+    }
+
+    fn windows_and_workspaces(&self) -> Result<(Vec<Window>, HashMap<u64, Workspace>)> {
+        let mut socket = self.get_socket()?;
+        let reply = socket
+            .send(Request::Windows)
+            .context("Failed to send windows request")?;
+
+        let windows = match reply {
+            Ok(Response::Windows(win)) => win,
+            Ok(_) => anyhow::bail!("unexpected response"),
+            Err(e) => anyhow::bail!("niri returned error {e}"),
+        };
+
+        let reply = socket
+            .send(Request::Workspaces)
+            .context("Failed to send workspaces request")?;
+        let workspaces = match reply {
+            Ok(Response::Workspaces(workspaces)) => workspaces,
+            Ok(_) => anyhow::bail!("unexpected response"),
+            Err(e) => anyhow::bail!("niri returned error {e}"),
+        };
+        let workspaces = workspaces.into_iter().map(|ws| (ws.id, ws)).collect();
+        Ok((windows, workspaces))
+    }
+}
+#[async_trait::async_trait]
+impl Compositor for NiriCompositor {
+    fn get_windows(&self) -> Result<Vec<Process>> {
+        let (windows, workspaces) = self.windows_and_workspaces()?;
+        Ok(niri_processes(&windows, &workspaces))
+    }
+
+    fn can(&self, capability: Capability) -> bool {
+        // niri has no pinned windows
+        capability != Capability::Pin
+    }
+
+    fn get_workspaces(&self) -> Result<Vec<super::Workspace>> {
+        let (windows, workspaces) = self.windows_and_workspaces()?;
+        let numbers = niri_workspace_numbers(&workspaces);
+        let mut listed = workspaces.values().collect::<Vec<_>>();
+        listed.sort_by_key(|ws| numbers.get(&ws.id));
+        Ok(listed
+            .into_iter()
+            .map(|ws| super::Workspace {
+                id: ws.id,
+                number: numbers[&ws.id],
+                // unnamed workspaces go by their number, see `Workspace::label`
+                name: ws.name.clone().unwrap_or_default(),
+                monitor: ws.output.clone().unwrap_or_default(),
+                windows: windows
+                    .iter()
+                    .filter(|w| w.workspace_id == Some(ws.id))
+                    .filter_map(|w| niri_process(w, &numbers, &workspaces))
+                    .collect(),
+            })
+            .collect())
+    }
+
+    fn window_events(&self) -> BoxStream<'static, Result<WindowEvent>> {
+        // niri's event stream takes over the whole socket, and reading it blocks,
+        // so it gets its own connection on its own thread
+        let (tx, rx) = mpsc::unbounded();
+        std::thread::spawn(move || {
+            if let Err(e) = niri_event_loop(&tx) {
+                let _ = tx.unbounded_send(Err(e));
+            }
+        });
+        rx.boxed()
+    }
+
+    async fn focus_window(&self, process: Process) -> Result<()> {
+        let mut socket = self.get_socket()?;
+        let reply = socket
+            .send(Request::Action(Action::FocusWindow {
+                id: process.window_id.unwrap(),
+            }))
+            .context("failed to send focus request")?;
+        match reply {
+            Ok(_) => Ok(()),
+            Err(e) => anyhow::bail!("niri returned error: {e}"),
+        }
+    }
+
+    async fn close_window(&self, process: Process) -> Result<()> {
+        let mut socket = self.get_socket()?;
+        let reply = socket
+            .send(Request::Action(Action::CloseWindow {
+                id: process.window_id,
+            }))
+            .context("Failed to close window")?;
+
+        match reply {
+            Ok(_) => Ok(()),
+            Err(e) => anyhow::bail!("niri returned error: {e}"),
+        }
+    }
+
+    async fn focus_workspace(&self, workspace: super::Workspace) -> Result<()> {
+        let mut socket = self.get_socket()?;
+        let reply = socket
+            .send(Request::Action(Action::FocusWorkspace {
+                reference: WorkspaceReferenceArg::Id(workspace.id),
+            }))
+            .context("Failed to send focus workspace request")?;
+        match reply {
+            Ok(_) => Ok(()),
+            Err(e) => anyhow::bail!("niri returned error: {e}"),
+        }
+    }
+
+    async fn move_to_workspace(&self, process: Process, target: String) -> Result<()> {
+        // a number is the one the rows show, see `niri_workspace_numbers`
+        let reference = match target.parse::<u64>() {
+            Ok(number) => {
+                let (_, workspaces) = self.windows_and_workspaces()?;
+                let id = niri_workspace_numbers(&workspaces)
+                    .into_iter()
+                    .find_map(|(id, n)| (n == number).then_some(id))
+                    .with_context(|| format!("there is no workspace {number}"))?;
+                WorkspaceReferenceArg::Id(id)
+            }
+            Err(_) => WorkspaceReferenceArg::Name(target),
+        };
+        let mut socket = self.get_socket()?;
+        let reply = socket
+            .send(Request::Action(Action::MoveWindowToWorkspace {
+                window_id: Some(process.window_id.context("window is missing its id")?),
+                reference,
+                focus: false,
+            }))
+            .context("Failed to send move request")?;
+        match reply {
+            Ok(_) => Ok(()),
+            Err(e) => anyhow::bail!("niri returned error: {e}"),
+        }
+    }
+
+    async fn toggle_floating(&self, process: Process) -> Result<()> {
+        self.window_action(Action::ToggleWindowFloating {
+            id: process.window_id,
+        })
+    }
+
+    async fn toggle_fullscreen(&self, process: Process) -> Result<()> {
+        self.window_action(Action::FullscreenWindow {
+            id: process.window_id,
+        })
+    }
+
+    async fn move_to_monitor(&self, process: Process, monitor: String) -> Result<()> {
+        self.window_action(Action::MoveWindowToMonitor {
+            id: process.window_id,
+            output: monitor,
+        })
+    }
+
+    async fn launch(&self, command: String) -> Result<()> {
+        let mut socket = self.get_socket()?;
+        let reply = socket
+            .send(Request::Action(Action::Spawn {
+                command: vec!["sh".to_string(), "-c".to_string(), command],
+            }))
+            .context("Failed to send spawn request")?;
+        match reply {
+            Ok(_) => Ok(()),
+            Err(e) => anyhow::bail!("niri returned error: {e}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use niri_ipc::WindowLayout;
+
+    use super::*;
+
+    fn workspace(id: u64, idx: u8, output: &str) -> Workspace {
+        Workspace {
+            id,
+            idx,
+            name: None,
+            output: Some(output.to_string()),
+            is_urgent: false,
+            is_active: false,
+            is_focused: false,
+            active_window_id: None,
+        }
+    }
+
+    fn window(id: u64, workspace_id: u64) -> Window {
+        Window {
+            id,
+            title: Some(format!("window {id}")),
+            app_id: Some("foot".to_string()),
+            pid: Some(100),
+            workspace_id: Some(workspace_id),
+            is_focused: false,
+            is_floating: false,
+            is_urgent: false,
+            layout: WindowLayout {
+                pos_in_scrolling_layout: None,
+                tile_size: (0.0, 0.0),
+                window_size: (0, 0),
+                tile_pos_in_workspace_view: None,
+                window_offset_in_tile: (1.0, 1.0),
+            },
+            focus_timestamp: None,
+        }
+    }
+
+    #[test]
+    fn windows_and_workspaces_share_numbers_across_outputs() {
+        // ids out of order, and both outputs have a workspace 1
+        let workspaces = [
+            workspace(7, 1, "HDMI-A-1"),
+            workspace(3, 2, "DP-1"),
+            workspace(9, 1, "DP-1"),
+            workspace(4, 2, "HDMI-A-1"),
+        ]
+        .into_iter()
+        .map(|ws| (ws.id, ws))
+        .collect();
+        let numbers = niri_workspace_numbers(&workspaces);
+        assert_eq!(numbers[&9], 1);
+        assert_eq!(numbers[&3], 2);
+        assert_eq!(numbers[&7], 3);
+        assert_eq!(numbers[&4], 4);
+
+        let windows = [window(1, 4), window(2, 9), window(3, 7)];
+        let processes = niri_processes(&windows, &workspaces);
+        let placed = processes
+            .iter()
+            .map(|p| (p.window_id, p.workspace, p.monitor.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            placed,
+            [
+                (Some(2), 1, "DP-1"),
+                (Some(3), 3, "HDMI-A-1"),
+                (Some(1), 4, "HDMI-A-1"),
+            ]
+        );
+    }
+}
@@ -0,0 +1,525 @@
+use std::io::{Read, Write};
+use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+
+use anyhow::{Context, Result};
+use iced::futures::{StreamExt, channel::mpsc, stream::BoxStream};
+use serde::Deserialize;
+
+use super::{Capability, Compositor, FullscreenStatus, Process, WindowEvent, Workspace};
+
+/// Every i3-ipc message starts with this, followed by the payload length and type (both u32)
+const MAGIC: &[u8; 6] = b"i3-ipc";
+
+const RUN_COMMAND: u32 = 0;
+const GET_WORKSPACES: u32 = 1;
+const SUBSCRIBE: u32 = 2;
+const GET_TREE: u32 = 4;
+const GET_VERSION: u32 = 7;
+/// Event replies have the high bit set, window events are event number 3
+const WINDOW_EVENT: u32 = 0x8000_0003;
+
+/// The view shows anything above 50 as a special workspace, which is what the scratchpad is
+const SCRATCHPAD_WORKSPACE: u64 = 99;
+
+/// Just the parts of a `GET_TREE` node that end up in a `Process`
+#[derive(Deserialize)]
+struct Node {
+    id: u64,
+    #[serde(rename = "type")]
+    kind: String,
+    name: Option<String>,
+    num: Option<i32>,
+    pid: Option<i32>,
+    app_id: Option<String>,
+    /// only there for xwayland windows
+    window_properties: Option<WindowProperties>,
+    #[serde(default)]
+    focused: bool,
+    /// 0 = none, 1 = fullscreen on its output, 2 = global fullscreen
+    #[serde(default)]
+    fullscreen_mode: u8,
+    /// ids of the children, most recently focused first
+    #[serde(default)]
+    focus: Vec<u64>,
+    #[serde(default)]
+    nodes: Vec<Node>,
+    #[serde(default)]
+    floating_nodes: Vec<Node>,
+}
+
+#[derive(Deserialize)]
+struct WindowProperties {
+    class: Option<String>,
+}
+
+/// One entry of the `GET_WORKSPACES` reply
+#[derive(Deserialize)]
+struct SwayWorkspace {
+    /// -1 for named workspaces without a number
+    num: i32,
+    name: String,
+    output: String,
+}
+
+#[derive(Deserialize)]
+struct CommandOutcome {
+    success: bool,
+    error: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct SwayWindowEvent {
+    change: String,
+    container: Node,
+}
+
+/// `value` as a single argument of a sway command
+fn quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Writes one message in the i3-ipc framing
+fn send_message(stream: &mut UnixStream, message_type: u32, payload: &str) -> Result<()> {
+    let len = u32::try_from(payload.len()).context("i3-ipc payload too large")?;
+    let mut message = Vec::with_capacity(14 + payload.len());
+    message.extend_from_slice(MAGIC);
+    message.extend_from_slice(&len.to_ne_bytes());
+    message.extend_from_slice(&message_type.to_ne_bytes());
+    message.extend_from_slice(payload.as_bytes());
+    stream
+        .write_all(&message)
+        .context("Failed to write to sway socket")
+}
+
+/// Reads one message in the i3-ipc framing, returning its type and payload
+fn read_message(stream: &mut UnixStream) -> Result<(u32, Vec<u8>)> {
+    let mut header = [0u8; 14];
+    stream
+        .read_exact(&mut header)
+        .context("Failed to read from sway socket")?;
+    if &header[..6] != MAGIC {
+        anyhow::bail!("sway socket sent an invalid message");
+    }
+    let len = u32::from_ne_bytes([header[6], header[7], header[8], header[9]]);
+    let message_type = u32::from_ne_bytes([header[10], header[11], header[12], header[13]]);
+    let mut payload = vec![0u8; len as usize];
+    stream
+        .read_exact(&mut payload)
+        .context("Failed to read from sway socket")?;
+    Ok((message_type, payload))
+}
+
+/// Sends a request and waits for its reply, skipping any events in between
+fn request(stream: &mut UnixStream, message_type: u32, payload: &str) -> Result<Vec<u8>> {
+    send_message(stream, message_type, payload)?;
+    loop {
+        let (reply_type, reply) = read_message(stream)?;
+        if reply_type == message_type {
+            return Ok(reply);
+        }
+    }
+}
+
+fn get_tree(stream: &mut UnixStream) -> Result<Node> {
+    let reply = request(stream, GET_TREE, "")?;
+    serde_json::from_slice(&reply).context("Failed to parse sway tree")
+}
+
+fn is_window(node: &Node) -> bool {
+    (node.kind == "con" || node.kind == "floating_con")
+        && node.nodes.is_empty()
+        && node.floating_nodes.is_empty()
+        && node.pid.is_some()
+}
+
+/// Children of `node`, most recently focused first
+fn children_by_focus(node: &Node) -> Vec<&Node> {
+    let mut children = node
+        .nodes
+        .iter()
+        .chain(node.floating_nodes.iter())
+        .collect::<Vec<_>>();
+    children.sort_by_key(|child| {
+        node.focus
+            .iter()
+            .position(|&id| id == child.id)
+            .unwrap_or(usize::MAX)
+    });
+    children
+}
+
+/// Where in the tree a window was found
+#[derive(Clone, Copy, Default)]
+struct Location<'a> {
+    workspace: u64,
+    workspace_name: &'a str,
+    output: &'a str,
+    floating: bool,
+}
+
+/// Walks the tree in focus order, so windows come out most recently used first
+fn collect_windows<'a>(
+    node: &'a Node,
+    location: Location<'a>,
+    out: &mut Vec<(&'a Node, Location<'a>)>,
+) {
+    let mut location = location;
+    if node.kind == "output" {
+        location.output = node.name.as_deref().unwrap_or_default();
+    } else if node.kind == "workspace" {
+        location.workspace_name = node.name.as_deref().unwrap_or_default();
+        location.workspace = if location.workspace_name == "__i3_scratch" {
+            SCRATCHPAD_WORKSPACE
+        } else {
+            // named workspaces without a number are -1
+            node.num.and_then(|n| u64::try_from(n).ok()).unwrap_or(0)
+        };
+    }
+    if is_window(node) {
+        out.push((node, location));
+        return;
+    }
+    for child in children_by_focus(node) {
+        let child_location = Location {
+            floating: location.floating || node.floating_nodes.iter().any(|f| f.id == child.id),
+            ..location
+        };
+        collect_windows(child, child_location, out);
+    }
+}
+
+fn sway_process(node: &Node, location: Location<'_>, focus_stamp: u64) -> Process {
+    let fullscreen = if node.fullscreen_mode == 0 {
+        FullscreenStatus::None
+    } else {
+        FullscreenStatus::Fullscreen
+    };
+    Process {
+        pid: node.pid.unwrap_or(0),
+        title: node.name.clone().unwrap_or_else(|| "Unknown".to_string()),
+        class: node
+            .app_id
+            .clone()
+            .or_else(|| node.window_properties.as_ref()?.class.clone())
+            .unwrap_or_default(),
+        window_id: Some(node.id),
+        workspace: location.workspace,
+        workspace_name: location.workspace_name.to_string(),
+        monitor: location.output.to_string(),
+        fullscreen,
+        floating: location.floating,
+        focused: node.focused,
+        focus_stamp,
+    }
+}
+
+fn sway_processes(tree: &Node) -> Vec<Process> {
+    let mut windows = Vec::new();
+    collect_windows(tree, Location::default(), &mut windows);
+    let count = windows.len() as u64;
+    let mut processes = windows
+        .into_iter()
+        .enumerate()
+        .map(|(idx, (node, location))| sway_process(node, location, count - idx as u64))
+        .filter(|process| process.title != "whereami")
+        .collect::<Vec<_>>();
+    processes.sort_by_key(|process| process.workspace);
+    processes
+}
+
+/// Reads window events from sway until it fails or the receiving end is gone.
+/// A window event's container does not say which workspace it is on,
+/// so anything other than a title/focus change or a close fetches the tree again
+fn sway_event_loop(path: &Path, tx: &mpsc::UnboundedSender<Result<WindowEvent>>) -> Result<()> {
+    let mut events = UnixStream::connect(path).context("failed to connect to sway socket")?;
+    let mut commands = UnixStream::connect(path).context("failed to connect to sway socket")?;
+    let reply = request(&mut events, SUBSCRIBE, r#"["window"]"#)?;
+    let reply: CommandOutcome =
+        serde_json::from_slice(&reply).context("Failed to parse sway reply")?;
+    if !reply.success {
+        anyhow::bail!("sway refused the window event subscription");
+    }
+
+    loop {
+        let (message_type, payload) = read_message(&mut events)?;
+        if message_type != WINDOW_EVENT {
+            continue;
+        }
+        let event: SwayWindowEvent =
+            serde_json::from_slice(&payload).context("Failed to parse sway event")?;
+        let container = event.container;
+        let event = match event.change.as_str() {
+            "close" => WindowEvent::Closed(container.id),
+            "focus" => WindowEvent::Focused(Some(container.id)),
+            "title" => WindowEvent::TitleChanged {
+                window_id: container.id,
+                title: container.name.unwrap_or_default(),
+            },
+            "new" | "move" | "floating" | "fullscreen_mode" => {
+                WindowEvent::Reset(sway_processes(&get_tree(&mut commands)?))
+            }
+            _ => continue,
+        };
+        if tx.unbounded_send(Ok(event)).is_err() {
+            return Ok(());
+        }
+    }
+}
+
+/// Talks to sway over the i3 IPC protocol on `$SWAYSOCK`
+pub struct SwayCompositor {
+    path: PathBuf,
+    socket: Mutex<UnixStream>,
+}
+
+impl SwayCompositor {
+    pub fn new() -> Result<Self> {
+        let path = std::env::var("SWAYSOCK").context("SWAYSOCK not set")?;
+        Self::connect_to(path)
+    }
+
+    /// Connects to any socket speaking the i3-ipc framing
+    pub fn connect_to(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let socket = UnixStream::connect(&path).context("failed to connect to sway socket")?;
+        Ok(Self {
+            path,
+            socket: Mutex::new(socket),
+        })
+    }
+
+    fn get_socket(&self) -> Result<MutexGuard<'_, UnixStream>> {
+        let mut socket = self.socket.lock().unwrap();
+        // the connection does not survive sway restarting, so check it is still there
+        if request(&mut socket, GET_VERSION, "").is_err() {
+            *socket =
+                UnixStream::connect(&self.path).context("failed to reconnect to sway socket")?;
+        }
+        Ok(socket)
+    }
+
+    fn run_command(&self, command: &str) -> Result<()> {
+        let mut socket = self.get_socket()?;
+        let reply = request(&mut socket, RUN_COMMAND, command)?;
+        let outcomes: Vec<CommandOutcome> =
+            serde_json::from_slice(&reply).context("Failed to parse sway reply")?;
+        match outcomes.into_iter().find(|outcome| !outcome.success) {
+            Some(outcome) => {
+                anyhow::bail!("sway returned error: {}", outcome.error.unwrap_or_default())
+            }
+            None => Ok(()),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl Compositor for SwayCompositor {
+    fn get_windows(&self) -> Result<Vec<Process>> {
+        let mut socket = self.get_socket()?;
+        let tree = get_tree(&mut socket)?;
+        Ok(sway_processes(&tree))
+    }
+
+    fn can(&self, _capability: Capability) -> bool {
+        true
+    }
+
+    fn get_workspaces(&self) -> Result<Vec<Workspace>> {
+        let mut socket = self.get_socket()?;
+        let reply = request(&mut socket, GET_WORKSPACES, "")?;
+        let workspaces: Vec<SwayWorkspace> =
+            serde_json::from_slice(&reply).context("Failed to parse sway workspaces")?;
+        let windows = sway_processes(&get_tree(&mut socket)?);
+        Ok(workspaces
+            .into_iter()
+            .map(|ws| Workspace {
+                id: u64::try_from(ws.num).unwrap_or(0),
+                number: u64::try_from(ws.num).unwrap_or(0),
+                windows: windows
+                    .iter()
+                    .filter(|w| w.workspace_name == ws.name)
+                    .cloned()
+                    .collect(),
+                name: ws.name,
+                monitor: ws.output,
+            })
+            .collect())
+    }
+
+    fn window_events(&self) -> BoxStream<'static, Result<WindowEvent>> {
+        // subscribing turns a connection into an event-only one, so it gets its own
+        let (tx, rx) = mpsc::unbounded();
+        let path = self.path.clone();
+        std::thread::spawn(move || {
+            if let Err(e) = sway_event_loop(&path, &tx) {
+                let _ = tx.unbounded_send(Err(e));
+            }
+        });
+        rx.boxed()
+    }
+
+    async fn focus_window(&self, process: Process) -> Result<()> {
+        let id = process
+            .window_id
+            .context("sway window is missing its container id")?;
+        // focusing a scratchpad window brings it out of the scratchpad
+        self.run_command(&format!("[con_id={id}] focus"))
+            .context(format!("Could not focus window {id}"))
+    }
+
+    async fn close_window(&self, process: Process) -> Result<()> {
+        let id = process
+            .window_id
+            .context("sway window is missing its container id")?;
+        self.run_command(&format!("[con_id={id}] kill"))
+            .context(format!("Could not close window {id}"))
+    }
+
+    async fn focus_workspace(&self, workspace: Workspace) -> Result<()> {
+        // sway names numbered workspaces after their number, so the name always works
+        self.run_command(&format!("workspace {}", quote(&workspace.name)))
+            .context(format!("Could not switch to workspace {}", workspace.name))
+    }
+
+    async fn move_to_workspace(&self, process: Process, target: String) -> Result<()> {
+        let id = process
+            .window_id
+            .context("sway window is missing its container id")?;
+        let workspace = if target.parse::<u32>().is_ok() {
+            format!("number {target}")
+        } else {
+            quote(&target)
+        };
+        self.run_command(&format!(
+            "[con_id={id}] move container to workspace {workspace}"
+        ))
+        .context(format!("Could not move window {id} to workspace {target}"))
+    }
+
+    async fn toggle_floating(&self, process: Process) -> Result<()> {
+        let id = process
+            .window_id
+            .context("sway window is missing its container id")?;
+        self.run_command(&format!("[con_id={id}] floating toggle"))
+            .context(format!("Could not float window {id}"))
+    }
+
+    async fn toggle_fullscreen(&self, process: Process) -> Result<()> {
+        let id = process
+            .window_id
+            .context("sway window is missing its container id")?;
+        self.run_command(&format!("[con_id={id}] fullscreen toggle"))
+            .context(format!("Could not make window {id} fullscreen"))
+    }
+
+    /// sway calls it sticky, and like Hyprland only does it for floating windows
+    async fn toggle_pin(&self, process: Process) -> Result<()> {
+        let id = process
+            .window_id
+            .context("sway window is missing its container id")?;
+        self.run_command(&format!("[con_id={id}] sticky toggle"))
+            .context(format!("Could not pin window {id}"))
+    }
+
+    async fn move_to_monitor(&self, process: Process, monitor: String) -> Result<()> {
+        let id = process
+            .window_id
+            .context("sway window is missing its container id")?;
+        self.run_command(&format!(
+            "[con_id={id}] move container to output {}",
+            quote(&monitor)
+        ))
+        .context(format!("Could not move window {id} to {monitor}"))
+    }
+
+    async fn launch(&self, command: String) -> Result<()> {
+        // quoted, or sway would take any `;` or `,` in it as the start of its next command
+        self.run_command(&format!("exec sh -c {}", quote(&command)))
+            .context(format!("Could not launch {command}"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::unix::net::UnixListener;
+    use std::sync::mpsc as std_mpsc;
+
+    use super::*;
+
+    const TREE: &str = r#"{
+        "id": 1, "type": "root", "focus": [2], "nodes": [{
+            "id": 2, "type": "output", "name": "DP-1", "focus": [3], "nodes": [{
+                "id": 3, "type": "workspace", "name": "2", "num": 2, "focus": [11, 10],
+                "nodes": [
+                    {"id": 10, "type": "con", "name": "vim", "pid": 100, "app_id": "foot"},
+                    {"id": 11, "type": "con", "name": "Mozilla Firefox", "pid": 200,
+                     "app_id": null, "window_properties": {"class": "firefox"},
+                     "focused": true, "fullscreen_mode": 1}
+                ],
+                "floating_nodes": [
+                    {"id": 12, "type": "floating_con", "name": "whereami", "pid": 300,
+                     "app_id": "whereami"}
+                ]
+            }]
+        }]
+    }"#;
+
+    /// Answers i3-ipc requests like sway would, passing on every `RUN_COMMAND` it gets
+    fn fake_sway() -> (
+        tempfile::TempDir,
+        SwayCompositor,
+        std_mpsc::Receiver<String>,
+    ) {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("sway.sock");
+        let listener = UnixListener::bind(&path).unwrap();
+        let (tx, rx) = std_mpsc::channel();
+        std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            while let Ok((message_type, payload)) = read_message(&mut stream) {
+                let reply = match message_type {
+                    GET_TREE => TREE.to_string(),
+                    RUN_COMMAND => {
+                        tx.send(String::from_utf8(payload).unwrap()).unwrap();
+                        r#"[{"success": true}]"#.to_string()
+                    }
+                    _ => "{}".to_string(),
+                };
+                send_message(&mut stream, message_type, &reply).unwrap();
+            }
+        });
+        let compositor = SwayCompositor::connect_to(&path).unwrap();
+        (dir, compositor, rx)
+    }
+
+    #[test]
+    fn get_windows_parses_the_tree() {
+        let (_dir, compositor, _) = fake_sway();
+        let windows = compositor.get_windows().unwrap();
+        // whereami leaves itself out
+        assert_eq!(windows.len(), 2);
+        // focus order, most recent first
+        let (firefox, vim) = (&windows[0], &windows[1]);
+        assert_eq!(firefox.window_id, Some(11));
+        assert_eq!(firefox.pid, 200);
+        assert_eq!(firefox.class, "firefox");
+        assert_eq!(firefox.workspace, 2);
+        assert_eq!(firefox.monitor, "DP-1");
+        assert!(matches!(firefox.fullscreen, FullscreenStatus::Fullscreen));
+        assert!(firefox.focused);
+        assert!(firefox.focus_stamp > vim.focus_stamp);
+        assert_eq!(vim.title, "vim");
+        assert_eq!(vim.class, "foot");
+        assert!(!vim.floating);
+    }
+
+    #[test]
+    fn focus_window_runs_a_con_id_command() {
+        let (_dir, compositor, commands) = fake_sway();
+        let window = compositor.get_windows().unwrap().remove(1);
+        iced::futures::executor::block_on(compositor.focus_window(window)).unwrap();
+        assert_eq!(commands.recv().unwrap(), "[con_id=10] focus");
+    }
+}
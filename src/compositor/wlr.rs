@@ -0,0 +1,418 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use anyhow::{Context, Result};
+use iced::futures::{StreamExt, channel::mpsc, stream::BoxStream};
+use wayland_client::{
+    Connection, Dispatch, EventQueue, Proxy, QueueHandle,
+    backend::ObjectId,
+    event_created_child,
+    globals::{GlobalListContents, registry_queue_init},
+    protocol::{wl_registry, wl_seat::WlSeat},
+};
+use wayland_protocols::ext::foreign_toplevel_list::v1::client::{
+    ext_foreign_toplevel_handle_v1::{self, ExtForeignToplevelHandleV1},
+    ext_foreign_toplevel_list_v1::{self, ExtForeignToplevelListV1},
+};
+use wayland_protocols_wlr::foreign_toplevel::v1::client::{
+    zwlr_foreign_toplevel_handle_v1::{self, ZwlrForeignToplevelHandleV1},
+    zwlr_foreign_toplevel_manager_v1::{self, ZwlrForeignToplevelManagerV1},
+};
+
+use super::{Capability, Compositor, FullscreenStatus, Process, WindowEvent};
+
+/// One toplevel as announced by the compositor. Changes are collected in here
+/// and only become visible once the compositor sends `done`
+#[derive(Default, Clone)]
+struct Toplevel {
+    window_id: u64,
+    title: String,
+    app_id: String,
+    fullscreen: bool,
+    maximized: bool,
+    activated: bool,
+    focus_stamp: u64,
+    /// only set when listing through wlr-foreign-toplevel-management, ext-foreign-toplevel-list
+    /// can not focus or close anything
+    handle: Option<ZwlrForeignToplevelHandleV1>,
+}
+
+impl Toplevel {
+    fn to_process(&self) -> Process {
+        let fullscreen = if self.fullscreen {
+            FullscreenStatus::Fullscreen
+        } else if self.maximized {
+            FullscreenStatus::Maximised
+        } else {
+            FullscreenStatus::None
+        };
+        // neither protocol knows about pids or workspaces
+        Process {
+            pid: 0,
+            title: self.title.clone(),
+            class: self.app_id.clone(),
+            window_id: Some(self.window_id),
+            workspace: 0,
+            workspace_name: String::new(),
+            monitor: String::new(),
+            fullscreen,
+            floating: false,
+            focused: self.activated,
+            focus_stamp: self.focus_stamp,
+        }
+    }
+}
+
+#[derive(Default)]
+struct Toplevels {
+    /// what the compositor has told us so far, including changes that are not `done` yet
+    pending: HashMap<ObjectId, Toplevel>,
+    /// the state as of each toplevel's last `done`
+    current: HashMap<ObjectId, Toplevel>,
+    next_id: u64,
+    /// bumped on every activation so the most recently activated toplevel has the biggest stamp
+    focus_counter: u64,
+    listeners: Vec<mpsc::UnboundedSender<Result<WindowEvent>>>,
+}
+
+impl Toplevels {
+    fn new_toplevel(&mut self, id: ObjectId, handle: Option<ZwlrForeignToplevelHandleV1>) {
+        self.next_id += 1;
+        self.pending.insert(
+            id,
+            Toplevel {
+                window_id: self.next_id,
+                handle,
+                ..Default::default()
+            },
+        );
+    }
+
+    fn broadcast(&mut self, event: &WindowEvent) {
+        self.listeners
+            .retain(|tx| tx.unbounded_send(Ok(event.clone())).is_ok());
+    }
+
+    fn done(&mut self, id: &ObjectId) {
+        let Some(toplevel) = self.pending.get_mut(id) else {
+            return;
+        };
+        let was_activated = self.current.get(id).is_some_and(|t| t.activated);
+        if toplevel.activated && !was_activated {
+            self.focus_counter += 1;
+            toplevel.focus_stamp = self.focus_counter;
+        }
+        let toplevel = toplevel.clone();
+        self.current.insert(id.clone(), toplevel.clone());
+        if toplevel.title == "whereami" {
+            return;
+        }
+        self.broadcast(&WindowEvent::Changed(toplevel.to_process()));
+        if toplevel.activated && !was_activated {
+            self.broadcast(&WindowEvent::Focused(Some(toplevel.window_id)));
+        }
+    }
+
+    fn closed(&mut self, id: &ObjectId) {
+        self.pending.remove(id);
+        if let Some(toplevel) = self.current.remove(id) {
+            self.broadcast(&WindowEvent::Closed(toplevel.window_id));
+        }
+    }
+
+    fn close_all(&mut self, reason: &str) {
+        for tx in self.listeners.drain(..) {
+            let _ = tx.unbounded_send(Err(anyhow::anyhow!("{reason}")));
+        }
+    }
+}
+
+/// The Wayland dispatch state. Only holds a handle to the shared toplevel list,
+/// so the lock is not held while the dispatch thread waits for events
+struct WlrState {
+    toplevels: Arc<Mutex<Toplevels>>,
+}
+
+impl Dispatch<wl_registry::WlRegistry, GlobalListContents> for WlrState {
+    fn event(
+        _: &mut Self,
+        _: &wl_registry::WlRegistry,
+        _: wl_registry::Event,
+        _: &GlobalListContents,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<WlSeat, ()> for WlrState {
+    fn event(
+        _: &mut Self,
+        _: &WlSeat,
+        _: <WlSeat as Proxy>::Event,
+        (): &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<ZwlrForeignToplevelManagerV1, ()> for WlrState {
+    fn event(
+        state: &mut Self,
+        _: &ZwlrForeignToplevelManagerV1,
+        event: zwlr_foreign_toplevel_manager_v1::Event,
+        (): &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        match event {
+            zwlr_foreign_toplevel_manager_v1::Event::Toplevel { toplevel } => {
+                let mut toplevels = state.toplevels.lock().unwrap();
+                toplevels.new_toplevel(toplevel.id(), Some(toplevel));
+            }
+            zwlr_foreign_toplevel_manager_v1::Event::Finished => {
+                let mut toplevels = state.toplevels.lock().unwrap();
+                toplevels.close_all("compositor stopped sending toplevels");
+            }
+            _ => {}
+        }
+    }
+
+    event_created_child!(WlrState, ZwlrForeignToplevelManagerV1, [
+        zwlr_foreign_toplevel_manager_v1::EVT_TOPLEVEL_OPCODE => (ZwlrForeignToplevelHandleV1, ())
+    ]);
+}
+
+impl Dispatch<ZwlrForeignToplevelHandleV1, ()> for WlrState {
+    fn event(
+        state: &mut Self,
+        handle: &ZwlrForeignToplevelHandleV1,
+        event: zwlr_foreign_toplevel_handle_v1::Event,
+        (): &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        let mut toplevels = state.toplevels.lock().unwrap();
+        let id = handle.id();
+        match event {
+            zwlr_foreign_toplevel_handle_v1::Event::Title { title } => {
+                if let Some(toplevel) = toplevels.pending.get_mut(&id) {
+                    toplevel.title = title;
+                }
+            }
+            zwlr_foreign_toplevel_handle_v1::Event::AppId { app_id } => {
+                if let Some(toplevel) = toplevels.pending.get_mut(&id) {
+                    toplevel.app_id = app_id;
+                }
+            }
+            zwlr_foreign_toplevel_handle_v1::Event::State { state } => {
+                // an array of u32 enum values in native byte order
+                let states = state
+                    .chunks_exact(4)
+                    .map(|b| u32::from_ne_bytes([b[0], b[1], b[2], b[3]]))
+                    .collect::<Vec<_>>();
+                let has = |s: zwlr_foreign_toplevel_handle_v1::State| states.contains(&(s as u32));
+                if let Some(toplevel) = toplevels.pending.get_mut(&id) {
+                    toplevel.maximized = has(zwlr_foreign_toplevel_handle_v1::State::Maximized);
+                    toplevel.activated = has(zwlr_foreign_toplevel_handle_v1::State::Activated);
+                    toplevel.fullscreen = has(zwlr_foreign_toplevel_handle_v1::State::Fullscreen);
+                }
+            }
+            zwlr_foreign_toplevel_handle_v1::Event::Done => toplevels.done(&id),
+            zwlr_foreign_toplevel_handle_v1::Event::Closed => {
+                toplevels.closed(&id);
+                handle.destroy();
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Dispatch<ExtForeignToplevelListV1, ()> for WlrState {
+    fn event(
+        state: &mut Self,
+        _: &ExtForeignToplevelListV1,
+        event: ext_foreign_toplevel_list_v1::Event,
+        (): &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        match event {
+            ext_foreign_toplevel_list_v1::Event::Toplevel { toplevel } => {
+                let mut toplevels = state.toplevels.lock().unwrap();
+                toplevels.new_toplevel(toplevel.id(), None);
+            }
+            ext_foreign_toplevel_list_v1::Event::Finished => {
+                let mut toplevels = state.toplevels.lock().unwrap();
+                toplevels.close_all("compositor stopped sending toplevels");
+            }
+            _ => {}
+        }
+    }
+
+    event_created_child!(WlrState, ExtForeignToplevelListV1, [
+        ext_foreign_toplevel_list_v1::EVT_TOPLEVEL_OPCODE => (ExtForeignToplevelHandleV1, ())
+    ]);
+}
+
+impl Dispatch<ExtForeignToplevelHandleV1, ()> for WlrState {
+    fn event(
+        state: &mut Self,
+        handle: &ExtForeignToplevelHandleV1,
+        event: ext_foreign_toplevel_handle_v1::Event,
+        (): &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        let mut toplevels = state.toplevels.lock().unwrap();
+        let id = handle.id();
+        match event {
+            ext_foreign_toplevel_handle_v1::Event::Title { title } => {
+                if let Some(toplevel) = toplevels.pending.get_mut(&id) {
+                    toplevel.title = title;
+                }
+            }
+            ext_foreign_toplevel_handle_v1::Event::AppId { app_id } => {
+                if let Some(toplevel) = toplevels.pending.get_mut(&id) {
+                    toplevel.app_id = app_id;
+                }
+            }
+            ext_foreign_toplevel_handle_v1::Event::Done => toplevels.done(&id),
+            ext_foreign_toplevel_handle_v1::Event::Closed => {
+                toplevels.closed(&id);
+                handle.destroy();
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Works on any compositor implementing `zwlr_foreign_toplevel_manager_v1`
+/// (river, labwc, wayfire, Hyprland...) or at least `ext_foreign_toplevel_list_v1`,
+/// in which case windows can only be listed
+pub struct WlrCompositor {
+    connection: Connection,
+    seat: Option<WlSeat>,
+    toplevels: Arc<Mutex<Toplevels>>,
+}
+
+impl WlrCompositor {
+    pub fn new() -> Result<Self> {
+        let connection =
+            Connection::connect_to_env().context("failed to connect to wayland display")?;
+        let (globals, mut queue): (_, EventQueue<WlrState>) =
+            registry_queue_init(&connection).context("failed to list wayland globals")?;
+        let qh = queue.handle();
+
+        let seat = globals.bind::<WlSeat, _, _>(&qh, 1..=1, ()).ok();
+        let has_manager = globals
+            .bind::<ZwlrForeignToplevelManagerV1, _, _>(&qh, 1..=3, ())
+            .is_ok();
+        if !has_manager {
+            globals
+                .bind::<ExtForeignToplevelListV1, _, _>(&qh, 1..=1, ())
+                .context("compositor supports neither wlr-foreign-toplevel-management nor ext-foreign-toplevel-list")?;
+        }
+
+        let toplevels = Arc::new(Mutex::new(Toplevels::default()));
+        let mut state = WlrState {
+            toplevels: Arc::clone(&toplevels),
+        };
+        // the first roundtrip announces the toplevels, the second gets their details
+        queue
+            .roundtrip(&mut state)
+            .context("failed to get toplevels")?;
+        queue
+            .roundtrip(&mut state)
+            .context("failed to get toplevels")?;
+
+        std::thread::spawn(move || {
+            let reason = loop {
+                if let Err(e) = queue.blocking_dispatch(&mut state) {
+                    break format!("wayland connection failed: {e}");
+                }
+            };
+            state.toplevels.lock().unwrap().close_all(&reason);
+        });
+
+        Ok(Self {
+            connection,
+            seat,
+            toplevels,
+        })
+    }
+
+    fn handle(&self, process: &Process) -> Result<ZwlrForeignToplevelHandleV1> {
+        let window_id = process.window_id.context("window is missing its id")?;
+        let toplevels = self.toplevels.lock().unwrap();
+        let toplevel = toplevels
+            .current
+            .values()
+            .find(|t| t.window_id == window_id)
+            .context(format!("Window {window_id} no longer exists"))?;
+        toplevel
+            .handle
+            .clone()
+            .context("compositor only allows listing windows")
+    }
+}
+
+#[async_trait::async_trait]
+impl Compositor for WlrCompositor {
+    fn get_windows(&self) -> Result<Vec<Process>> {
+        let toplevels = self.toplevels.lock().unwrap();
+        let mut processes = toplevels
+            .current
+            .values()
+            .filter(|t| t.title != "whereami")
+            .map(Toplevel::to_process)
+            .collect::<Vec<_>>();
+        // there are no workspaces to sort by, so keep the order they were opened in
+        processes.sort_by_key(|process| process.window_id);
+        Ok(processes)
+    }
+
+    fn can(&self, capability: Capability) -> bool {
+        // foreign toplevel has no notion of workspaces, floating or pinning
+        capability == Capability::Fullscreen
+    }
+
+    fn window_events(&self) -> BoxStream<'static, Result<WindowEvent>> {
+        let (tx, rx) = mpsc::unbounded();
+        self.toplevels.lock().unwrap().listeners.push(tx);
+        rx.boxed()
+    }
+
+    async fn focus_window(&self, process: Process) -> Result<()> {
+        let handle = self.handle(&process)?;
+        let seat = self
+            .seat
+            .as_ref()
+            .context("compositor has no seat to focus the window on")?;
+        handle.activate(seat);
+        self.connection
+            .flush()
+            .context(format!("Could not focus window {}", process.title))
+    }
+
+    async fn close_window(&self, process: Process) -> Result<()> {
+        let handle = self.handle(&process)?;
+        handle.close();
+        self.connection
+            .flush()
+            .context(format!("Could not close window {}", process.title))
+    }
+
+    async fn toggle_fullscreen(&self, process: Process) -> Result<()> {
+        let handle = self.handle(&process)?;
+        if matches!(process.fullscreen, FullscreenStatus::Fullscreen) {
+            handle.unset_fullscreen();
+        } else {
+            handle.set_fullscreen(None);
+        }
+        self.connection
+            .flush()
+            .context(format!("Could not make {} fullscreen", process.title))
+    }
+}
@@ -3,11 +3,15 @@ use serde::Deserialize;
 use std::{
     fs,
     io::{self, Write},
+    path::Path,
+    str::FromStr,
 };
 
+use crate::keybindings::Keybindings;
+
 /// Collects the toml file into an easy class.
 /// Its contents are quite self-explanitory
-#[derive(Deserialize)]
+#[derive(Deserialize, Clone)]
 pub struct Config {
     pub theme: Option<String>,
     pub window: Window,
@@ -15,16 +19,18 @@ pub struct Config {
     pub font: Font,
     pub layout: Layout,
     pub behavior: Behavior,
+    #[serde(default)]
+    pub keybindings: Keybindings,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Clone)]
 pub struct Window {
     pub width: u32,
     pub height: u32,
 }
 
 /// NOTE: Selected text *may* not be used... keeping it in because it could be useful
-#[derive(Deserialize)]
+#[derive(Deserialize, Clone)]
 pub struct Colours {
     pub background: String,
     pub text: String,
@@ -34,9 +40,16 @@ pub struct Colours {
     pub search_border_col: String,
     pub status: ColoursStatus,
     pub border_col: String,
+    /// the characters of a search result that matched the query
+    #[serde(default = "default_highlight")]
+    pub highlight: String,
+}
+
+fn default_highlight() -> String {
+    "#fe8019".to_string()
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Clone)]
 pub struct ColoursStatus {
     pub fullscreen: String,
     pub maximized: String,
@@ -44,12 +57,12 @@ pub struct ColoursStatus {
     pub tiled: String,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Clone)]
 pub struct Font {
     pub size: f32,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Clone)]
 pub struct Layout {
     pub padding: f32,
     pub margin: f32,
@@ -58,9 +71,129 @@ pub struct Layout {
 }
 
 /// Still need to implement all of this...
-#[derive(Deserialize)]
+/// `refresh_interval` is only used when the compositor's event stream is unavailable
+#[derive(Deserialize, Clone)]
 pub struct Behavior {
     pub refresh_interval: u64,
+    #[serde(default)]
+    pub sort: SortMode,
+    /// show application icons next to each window
+    #[serde(default = "default_true")]
+    pub icons: bool,
+    /// what the search bar matches against
+    #[serde(default = "default_search_fields")]
+    pub search_fields: Vec<SearchField>,
+    /// what `Terminal=true` apps are started in, as `<terminal> -e <command>`.
+    /// `$TERMINAL` (then xterm) when not set
+    #[serde(default)]
+    pub terminal: Option<String>,
+    /// classes whose windows are only closed after asking, `"*"` for all of them
+    #[serde(default)]
+    pub confirm_close: Vec<String>,
+    /// how long a closed window can be brought back for, 0 to turn that off
+    #[serde(default = "default_undo_seconds")]
+    pub undo_seconds: u64,
+    /// the labels on the visible rows that Alt+label focuses straight away
+    #[serde(default)]
+    pub hints: HintStyle,
+}
+
+fn default_undo_seconds() -> u64 {
+    5
+}
+
+impl Behavior {
+    /// Whether closing a window of `class` has to be confirmed first
+    pub fn confirms_close(&self, class: &str) -> bool {
+        self.confirm_close
+            .iter()
+            .any(|confirmed| confirmed == "*" || confirmed.eq_ignore_ascii_case(class))
+    }
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_search_fields() -> Vec<SearchField> {
+    vec![
+        SearchField::Title,
+        SearchField::Class,
+        SearchField::Workspace,
+        SearchField::Monitor,
+        SearchField::Status,
+    ]
+}
+
+/// Parts of a window the search can match against
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SearchField {
+    Title,
+    /// the app class / `app_id`
+    Class,
+    /// workspace number and name
+    Workspace,
+    Monitor,
+    /// Fullscreen, Maximised, Float or Tiled
+    Status,
+}
+
+/// How the window list is ordered
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SortMode {
+    /// grouped by workspace, lowest first
+    #[default]
+    Workspace,
+    /// most recently used first, like alt-tab. The focused window goes second
+    /// so opening whereami and pressing Enter jumps back to the previous window
+    Mru,
+}
+
+/// What the rows are labelled with for picking them in one key
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum HintStyle {
+    /// 1 to 9
+    #[default]
+    Numbers,
+    /// the home row, like vimium
+    Letters,
+    Off,
+}
+
+impl HintStyle {
+    /// The labels in order, from the top visible row down
+    pub fn labels(self) -> &'static [char] {
+        match self {
+            HintStyle::Numbers => &['1', '2', '3', '4', '5', '6', '7', '8', '9'],
+            HintStyle::Letters => &['a', 's', 'd', 'f', 'g', 'h', 'j', 'k', 'l'],
+            HintStyle::Off => &[],
+        }
+    }
+}
+
+impl SortMode {
+    /// The name `FromStr` takes back
+    pub fn as_str(self) -> &'static str {
+        match self {
+            SortMode::Workspace => "workspace",
+            SortMode::Mru => "mru",
+        }
+    }
+}
+
+impl FromStr for SortMode {
+    type Err = String;
+
+    fn from_str(mode: &str) -> Result<Self, Self::Err> {
+        match mode {
+            "workspace" => Ok(SortMode::Workspace),
+            "mru" => Ok(SortMode::Mru),
+            _ => Err(format!("unknown sort {mode:?}, expected workspace or mru")),
+        }
+    }
 }
 
 impl Default for Config {
@@ -86,6 +219,7 @@ impl Default for Config {
                     tiled: "#83a598".to_string(),
                 },
                 border_col: "#ebdbb2".to_string(),
+                highlight: default_highlight(),
             },
             font: Font { size: 14.0 },
             layout: Layout {
@@ -95,13 +229,28 @@ impl Default for Config {
                 border_radius: 4.0,
             },
             behavior: Behavior {
-                refresh_interval: 10,
+                refresh_interval: 250,
+                sort: SortMode::Workspace,
+                icons: true,
+                search_fields: default_search_fields(),
+                terminal: None,
+                confirm_close: Vec::new(),
+                undo_seconds: default_undo_seconds(),
+                hints: HintStyle::Numbers,
             },
+            keybindings: Keybindings::default(),
         }
     }
 }
 impl Config {
-    pub fn new() -> io::Result<Self> {
+    /// Reads `path`, or ~/.config/whereami/config.toml (written with the defaults
+    /// if it doesn't exist yet) when there is none
+    pub fn new(path: Option<&Path>) -> io::Result<Self> {
+        if let Some(path) = path {
+            let file_contents = fs::read_to_string(path)?;
+            return toml::from_str(&file_contents)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e));
+        }
         let home = std::env::var("HOME").expect("HOME not set");
 
         let config_path = format!("{home}/.config/whereami/config.toml");
@@ -142,6 +291,7 @@ impl Config {
             search_background = \"#282828\"
             search_border_col = \"#808080\"
             border_col = \"#ebdbb2\"
+            highlight = \"#fe8019\" # matched characters in search results
 
             [colours.status]
             fullscreen = \"#fb4934\"
@@ -159,7 +309,20 @@ impl Config {
             border_radius = 4
 
             [behavior]
-            refresh_interval = 10 # in milliseconds
+            refresh_interval = 250 # in milliseconds, only used if the compositor's event stream fails
+            sort = \"workspace\" # or \"mru\" for most recently used first
+            icons = true
+            search_fields = [\"title\", \"class\", \"workspace\", \"monitor\", \"status\"]
+            # terminal = \"foot\" # for apps that run in a terminal, $TERMINAL by default
+            confirm_close = [] # classes to ask about before closing, e.g. [\"code\"], or [\"*\"] for all
+            undo_seconds = 5 # how long a closed window can be reopened with Ctrl+Z, 0 to turn off
+            hints = \"numbers\" # row labels Alt+label focuses: \"numbers\", \"letters\" (asdf...) or \"off\"
+
+            [keybindings]
+            preset = \"default\" # or \"vim\" (j/k, /, x, q) or \"emacs\" (ctrl+g, ctrl+s, alt+v)
+            # \"ctrl+j\" = \"down\"
+            # \"ctrl+shift+w\" = \"close\"
+            # \"m\" = \"none\" # unbind a key
             ";
 
         file.write_all(config_content)?;
@@ -0,0 +1,142 @@
+use std::fs;
+use std::io::{self, BufRead, BufReader, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::PathBuf;
+
+use iced::futures::StreamExt;
+use iced::futures::channel::mpsc;
+use iced::futures::stream::{self, BoxStream};
+
+use crate::config_management::SortMode;
+
+/// What another launch of whereami can ask the running one to do
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Command {
+    Show,
+    Hide,
+    Toggle,
+    /// move the selection down (or up), showing the switcher first if it is hidden.
+    /// This is what further Alt+Tab presses send, since the compositor keeps them
+    Next,
+    Previous,
+}
+
+impl Command {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Command::Show => "show",
+            Command::Hide => "hide",
+            Command::Toggle => "toggle",
+            Command::Next => "next",
+            Command::Previous => "previous",
+        }
+    }
+
+    pub fn parse(command: &str) -> Option<Self> {
+        match command {
+            "show" => Some(Command::Show),
+            "hide" => Some(Command::Hide),
+            "toggle" => Some(Command::Toggle),
+            "next" => Some(Command::Next),
+            "previous" => Some(Command::Previous),
+            _ => None,
+        }
+    }
+}
+
+/// The options of the launch that sent a command, for the one showing the switcher.
+/// They only last until it is hidden again
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Overrides {
+    pub mode: Option<String>,
+    pub query: Option<String>,
+    pub sort: Option<SortMode>,
+}
+
+impl Overrides {
+    pub fn is_empty(&self) -> bool {
+        *self == Overrides::default()
+    }
+
+    /// One `name value` line each, after the command's line
+    fn write(&self, out: &mut impl Write) -> io::Result<()> {
+        if let Some(mode) = &self.mode {
+            writeln!(out, "mode {mode}")?;
+        }
+        if let Some(query) = &self.query {
+            // a line break would end it early
+            writeln!(out, "query {}", query.replace('\n', " "))?;
+        }
+        if let Some(sort) = self.sort {
+            writeln!(out, "sort {}", sort.as_str())?;
+        }
+        Ok(())
+    }
+
+    fn read(&mut self, line: &str) {
+        let (name, value) = line.split_once(' ').unwrap_or((line, ""));
+        match name {
+            "mode" => self.mode = Some(value.to_string()),
+            "query" => self.query = Some(value.to_string()),
+            "sort" => self.sort = value.parse().ok(),
+            _ => eprintln!("Unknown control option: {line:?}"),
+        }
+    }
+}
+
+/// `$XDG_RUNTIME_DIR/whereami.sock`, or in /tmp if that isn't set
+pub fn socket_path() -> PathBuf {
+    let runtime_dir = std::env::var("XDG_RUNTIME_DIR")
+        .ok()
+        .filter(|dir| !dir.is_empty())
+        .unwrap_or_else(|| "/tmp".to_string());
+    PathBuf::from(format!("{runtime_dir}/whereami.sock"))
+}
+
+/// Hands a command to the running instance. Fails when nothing is listening
+pub fn send(command: Command, overrides: &Overrides) -> io::Result<()> {
+    let mut stream = UnixStream::connect(socket_path())?;
+    writeln!(stream, "{}", command.as_str())?;
+    overrides.write(&mut stream)
+}
+
+/// Commands from other launches, one connection each: the command on the first line
+/// and the `Overrides` on the rest.
+/// Only the instance holding the pid lock gets here, so whatever socket file is
+/// still lying around is from one that died and can be replaced
+pub fn listen() -> BoxStream<'static, (Command, Overrides)> {
+    let path = socket_path();
+    let _ = fs::remove_file(&path);
+    let listener = match UnixListener::bind(&path) {
+        Ok(listener) => listener,
+        Err(e) => {
+            eprintln!(
+                "Failed to open the control socket at {}: {e}",
+                path.display()
+            );
+            return stream::empty().boxed();
+        }
+    };
+
+    let (tx, rx) = mpsc::unbounded();
+    std::thread::spawn(move || {
+        for connection in listener.incoming().flatten() {
+            let mut lines = BufReader::new(connection).lines();
+            let Some(Ok(line)) = lines.next() else {
+                continue;
+            };
+            let Some(command) = Command::parse(line.trim()) else {
+                eprintln!("Unknown control command: {:?}", line.trim());
+                continue;
+            };
+            let mut overrides = Overrides::default();
+            for line in lines.map_while(Result::ok) {
+                overrides.read(&line);
+            }
+            if tx.unbounded_send((command, overrides)).is_err() {
+                break;
+            }
+        }
+    });
+    rx.boxed()
+}
@@ -0,0 +1,256 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The parts of a `.desktop` file whereami cares about
+#[derive(Debug, Clone, Default)]
+pub struct DesktopEntry {
+    /// the desktop file id, e.g. `org.mozilla.firefox` for `org.mozilla.firefox.desktop`
+    pub id: String,
+    pub name: String,
+    /// what kind of app it is, e.g. "Web Browser"
+    pub generic_name: Option<String>,
+    /// extra words to find the app by
+    pub keywords: Vec<String>,
+    pub icon: Option<String>,
+    pub startup_wm_class: Option<String>,
+    /// the command line, with its `%f`-style field codes still in
+    pub exec: Option<String>,
+    /// has to be run inside a terminal emulator
+    pub terminal: bool,
+    /// `NoDisplay`/`Hidden`: there for the icon, but not to be launched from a menu
+    pub no_display: bool,
+}
+
+impl DesktopEntry {
+    /// Whether this is the entry for a window with the given class/`app_id`
+    pub fn matches_class(&self, class: &str) -> bool {
+        let class = class.to_lowercase();
+        let id = self.id.to_lowercase();
+        // reverse-DNS ids often have the plain app name as their last part (org.gnome.Nautilus)
+        let short_id = id.rsplit('.').next().unwrap_or(&id);
+        id == class
+            || short_id == class
+            || self
+                .startup_wm_class
+                .as_ref()
+                .is_some_and(|wm_class| wm_class.to_lowercase() == class)
+            || self.name.to_lowercase() == class
+    }
+
+    /// `Exec` ready to hand to a shell. Field codes are dropped, as nothing is ever
+    /// opened with a file or URL from here
+    pub fn command_line(&self) -> Option<String> {
+        let exec = self.exec.as_deref()?;
+        let mut command = String::with_capacity(exec.len());
+        let mut chars = exec.chars();
+        while let Some(ch) = chars.next() {
+            if ch == '%' {
+                // %% is a literal percent sign, anything else is a field code
+                if chars.next() == Some('%') {
+                    command.push('%');
+                }
+            } else {
+                command.push(ch);
+            }
+        }
+        Some(command.trim().to_string()).filter(|command| !command.is_empty())
+    }
+}
+
+/// `$XDG_DATA_HOME` followed by `$XDG_DATA_DIRS`, most important first
+pub fn data_dirs() -> Vec<PathBuf> {
+    let home = std::env::var("HOME").expect("HOME not set");
+    let data_home = std::env::var("XDG_DATA_HOME")
+        .ok()
+        .filter(|dir| !dir.is_empty())
+        .unwrap_or_else(|| format!("{home}/.local/share"));
+    let data_dirs = std::env::var("XDG_DATA_DIRS")
+        .ok()
+        .filter(|dirs| !dirs.is_empty())
+        .unwrap_or_else(|| "/usr/local/share:/usr/share".to_string());
+
+    std::iter::once(data_home.as_str())
+        .chain(data_dirs.split(':'))
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .collect()
+}
+
+/// The `applications` folder of every data dir that has one
+pub fn application_dirs() -> Vec<PathBuf> {
+    data_dirs()
+        .into_iter()
+        .map(|dir| dir.join("applications"))
+        .filter(|dir| dir.is_dir())
+        .collect()
+}
+
+/// Very small parser for the ini-like format shared by `.desktop` and `index.theme` files.
+/// Returns every group with its keys; localised keys (`Name[de]`) are skipped
+pub fn parse_ini(contents: &str) -> HashMap<String, HashMap<String, String>> {
+    let mut groups: HashMap<String, HashMap<String, String>> = HashMap::new();
+    let mut current = None;
+    for line in contents.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if let Some(group) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            current = Some(group.to_string());
+            groups.entry(group.to_string()).or_default();
+        } else if let (Some(group), Some((key, value))) = (&current, line.split_once('='))
+            && !key.contains('[')
+        {
+            groups
+                .entry(group.clone())
+                .or_default()
+                .insert(key.trim().to_string(), value.trim().to_string());
+        }
+    }
+    groups
+}
+
+fn parse_desktop_file(id: String, path: &Path) -> Option<DesktopEntry> {
+    let contents = fs::read_to_string(path).ok()?;
+    let mut groups = parse_ini(&contents);
+    let entry = groups.remove("Desktop Entry")?;
+    if entry.get("Type").is_some_and(|t| t != "Application") {
+        return None;
+    }
+    Some(DesktopEntry {
+        id,
+        name: entry.get("Name")?.clone(),
+        generic_name: entry.get("GenericName").cloned(),
+        keywords: entry
+            .get("Keywords")
+            .map(|keywords| {
+                keywords
+                    .split(';')
+                    .filter(|keyword| !keyword.is_empty())
+                    .map(String::from)
+                    .collect()
+            })
+            .unwrap_or_default(),
+        icon: entry.get("Icon").cloned(),
+        startup_wm_class: entry.get("StartupWMClass").cloned(),
+        exec: entry.get("Exec").cloned(),
+        terminal: entry.get("Terminal").is_some_and(|v| v == "true"),
+        no_display: ["NoDisplay", "Hidden"]
+            .iter()
+            .any(|key| entry.get(*key).is_some_and(|v| v == "true")),
+    })
+}
+
+/// Desktop file ids use `-` for subfolders, so `applications/kde/foo.desktop` is `kde-foo`
+fn collect_entries(
+    dir: &Path,
+    prefix: &str,
+    seen: &mut HashSet<String>,
+    out: &mut Vec<DesktopEntry>,
+) {
+    let Ok(read_dir) = fs::read_dir(dir) else {
+        return;
+    };
+    for file in read_dir.flatten() {
+        let path = file.path();
+        let Some(file_name) = path.file_name().and_then(|n| n.to_str()) else {
+            continue;
+        };
+        if path.is_dir() {
+            collect_entries(&path, &format!("{prefix}{file_name}-"), seen, out);
+        } else if let Some(stem) = file_name.strip_suffix(".desktop") {
+            let id = format!("{prefix}{stem}");
+            // an id found in an earlier (more important) data dir shadows the later ones
+            if !seen.insert(id.clone()) {
+                continue;
+            }
+            if let Some(entry) = parse_desktop_file(id, &path) {
+                out.push(entry);
+            }
+        }
+    }
+}
+
+/// Every application desktop entry in the XDG data dirs
+pub fn load_all() -> Vec<DesktopEntry> {
+    let mut seen = HashSet::new();
+    let mut entries = Vec::new();
+    for dir in application_dirs() {
+        collect_entries(&dir, "", &mut seen, &mut entries);
+    }
+    entries
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_ini_groups() {
+        let groups = parse_ini(
+            "# comment\n\
+             [Desktop Entry]\n\
+             Name = Files\n\
+             Name[de]=Dateien\n\
+             Exec=nautilus --new-window %U\n\
+             \n\
+             [Desktop Action new-window]\n\
+             Name=New Window\n",
+        );
+        let entry = &groups["Desktop Entry"];
+        assert_eq!(entry["Name"], "Files");
+        assert_eq!(entry["Exec"], "nautilus --new-window %U");
+        // localised keys are left out, the plain one is what gets shown
+        assert_eq!(entry.len(), 2);
+        assert_eq!(groups["Desktop Action new-window"]["Name"], "New Window");
+    }
+
+    #[test]
+    fn drops_field_codes() {
+        let cases = [
+            ("firefox %u", Some("firefox")),
+            ("code --new-window %F", Some("code --new-window")),
+            ("printf 100%% %i%c", Some("printf 100%")),
+            ("sh -c 'echo %%s' %k", Some("sh -c 'echo %s'")),
+            ("%U", None),
+        ];
+        for (exec, command) in cases {
+            let entry = DesktopEntry {
+                exec: Some(exec.to_string()),
+                ..Default::default()
+            };
+            assert_eq!(entry.command_line().as_deref(), command, "{exec}");
+        }
+        assert_eq!(DesktopEntry::default().command_line(), None);
+    }
+
+    #[test]
+    fn matches_window_classes() {
+        let nautilus = DesktopEntry {
+            id: "org.gnome.Nautilus".to_string(),
+            name: "Files".to_string(),
+            ..Default::default()
+        };
+        let chrome = DesktopEntry {
+            id: "com.google.Chrome".to_string(),
+            name: "Google Chrome".to_string(),
+            startup_wm_class: Some("Google-chrome".to_string()),
+            ..Default::default()
+        };
+        let cases = [
+            (&nautilus, "org.gnome.Nautilus", true),
+            // the last part of a reverse-DNS id
+            (&nautilus, "nautilus", true),
+            (&nautilus, "files", true),
+            (&nautilus, "gnome", false),
+            // only StartupWMClass says this one
+            (&chrome, "google-chrome", true),
+            (&chrome, "chrome", true),
+            (&chrome, "chromium", false),
+        ];
+        for (entry, class, matches) in cases {
+            assert_eq!(entry.matches_class(class), matches, "{} {class}", entry.id);
+        }
+    }
+}
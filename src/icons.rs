@@ -0,0 +1,323 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{LazyLock, Mutex, OnceLock, PoisonError};
+use std::time::SystemTime;
+
+use crate::desktop_entries::{self, DesktopEntry, parse_ini};
+
+/// The size icons are looked up at. Themes rarely have anything between 32 and 48
+const ICON_SIZE: u32 = 48;
+
+/// Shown when neither the app nor the theme has an icon to offer
+pub const GENERIC_ICON: &[u8] = br##"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 16 16">
+<rect x="1.5" y="2.5" width="13" height="11" rx="1.5" fill="none" stroke="#808080"/>
+<rect x="1.5" y="2.5" width="13" height="3" rx="1.5" fill="#808080"/>
+</svg>"##;
+
+/// read the first time an icon is looked up, like `apps::launchable`
+static ENTRIES: OnceLock<Vec<DesktopEntry>> = OnceLock::new();
+
+/// Kept between lookups so each theme's `index.theme` is only read once. Holding it also
+/// keeps two lookups from writing the cache at the same time
+static THEME: LazyLock<Mutex<ThemeLookup>> = LazyLock::new(|| Mutex::new(ThemeLookup::new()));
+
+/// Finds icons in a freedesktop icon theme, falling back to its parents, hicolor and pixmaps
+struct ThemeLookup {
+    /// `~/.icons` and `<data dir>/icons`, where themes live
+    base_dirs: Vec<PathBuf>,
+    theme: String,
+    /// each theme's `index.theme`s, read the first time an icon is looked up in it
+    themes: HashMap<String, Theme>,
+}
+
+/// What a theme's `index.theme` files say, across all the base directories
+struct Theme {
+    inherits: Vec<String>,
+    /// the directories icons can be in, closest to `ICON_SIZE` first
+    dirs: Vec<PathBuf>,
+}
+
+impl Theme {
+    fn load(base_dirs: &[PathBuf], name: &str) -> Self {
+        let mut inherits = Vec::new();
+        // (how far the directory is from the size we want, directory)
+        let mut dirs = Vec::new();
+        for base in base_dirs {
+            let theme_dir = base.join(name);
+            let Ok(index) = fs::read_to_string(theme_dir.join("index.theme")) else {
+                continue;
+            };
+            let groups = parse_ini(&index);
+            let Some(info) = groups.get("Icon Theme") else {
+                continue;
+            };
+            if let Some(parents) = info.get("Inherits") {
+                inherits.extend(parents.split(',').map(|p| p.trim().to_string()));
+            }
+            let Some(directories) = info.get("Directories") else {
+                continue;
+            };
+            for dir in directories.split(',').filter(|d| !d.is_empty()) {
+                if let Some(dir_info) = groups.get(dir) {
+                    dirs.push((size_distance(dir_info), theme_dir.join(dir)));
+                }
+            }
+        }
+        // stable, so the first base directory wins between equally good sizes
+        dirs.sort_by_key(|(distance, _)| *distance);
+        Self {
+            inherits,
+            dirs: dirs.into_iter().map(|(_, dir)| dir).collect(),
+        }
+    }
+}
+
+impl ThemeLookup {
+    fn new() -> Self {
+        let home = std::env::var("HOME").expect("HOME not set");
+        let base_dirs = std::iter::once(PathBuf::from(format!("{home}/.icons")))
+            .chain(
+                desktop_entries::data_dirs()
+                    .into_iter()
+                    .map(|dir| dir.join("icons")),
+            )
+            .filter(|dir| dir.is_dir())
+            .collect();
+        Self {
+            base_dirs,
+            theme: current_theme(&home),
+            themes: HashMap::new(),
+        }
+    }
+
+    fn lookup(&mut self, name: &str) -> Option<PathBuf> {
+        // Icon= is allowed to be a full path
+        let path = Path::new(name);
+        if path.is_absolute() {
+            return path.is_file().then(|| path.to_path_buf());
+        }
+        let mut visited = Vec::new();
+        let theme = self.theme.clone();
+        self.lookup_in_theme(&theme, name, &mut visited)
+            .or_else(|| self.lookup_in_theme("hicolor", name, &mut visited))
+            .or_else(|| {
+                ["png", "svg"]
+                    .iter()
+                    .map(|ext| PathBuf::from(format!("/usr/share/pixmaps/{name}.{ext}")))
+                    .find(|path| path.is_file())
+            })
+    }
+
+    fn lookup_in_theme(
+        &mut self,
+        theme: &str,
+        name: &str,
+        visited: &mut Vec<String>,
+    ) -> Option<PathBuf> {
+        if visited.iter().any(|t| t == theme) {
+            return None;
+        }
+        visited.push(theme.to_string());
+
+        let base_dirs = &self.base_dirs;
+        let info = self
+            .themes
+            .entry(theme.to_string())
+            .or_insert_with(|| Theme::load(base_dirs, theme));
+        let icon = info.dirs.iter().find_map(|dir| {
+            ["png", "svg"]
+                .iter()
+                .map(|ext| dir.join(format!("{name}.{ext}")))
+                .find(|path| path.is_file())
+        });
+        if icon.is_some() {
+            return icon;
+        }
+        let inherits = info.inherits.clone();
+        inherits
+            .iter()
+            .find_map(|parent| self.lookup_in_theme(parent, name, visited))
+    }
+}
+
+/// How far an icon theme directory is from `ICON_SIZE`, per the icon theme spec
+fn size_distance(dir_info: &HashMap<String, String>) -> u32 {
+    let get = |key: &str| dir_info.get(key).and_then(|v| v.parse::<u32>().ok());
+    let size = get("Size").unwrap_or(0);
+    let (min, max) = match dir_info.get("Type").map(String::as_str) {
+        Some("Scalable") => (
+            get("MinSize").unwrap_or(size),
+            get("MaxSize").unwrap_or(size),
+        ),
+        Some("Fixed") => (size, size),
+        // Threshold is the default type
+        _ => {
+            let threshold = get("Threshold").unwrap_or(2);
+            (size.saturating_sub(threshold), size + threshold)
+        }
+    };
+    if ICON_SIZE < min {
+        min - ICON_SIZE
+    } else {
+        ICON_SIZE.saturating_sub(max)
+    }
+}
+
+/// The GTK icon theme setting, which is what most desktops set. Defaults to hicolor
+fn current_theme(home: &str) -> String {
+    let config_home = std::env::var("XDG_CONFIG_HOME")
+        .ok()
+        .filter(|dir| !dir.is_empty())
+        .unwrap_or_else(|| format!("{home}/.config"));
+    ["gtk-4.0", "gtk-3.0"]
+        .iter()
+        .filter_map(|gtk| fs::read_to_string(format!("{config_home}/{gtk}/settings.ini")).ok())
+        .find_map(|settings| {
+            parse_ini(&settings)
+                .get("Settings")?
+                .get("gtk-icon-theme-name")
+                .cloned()
+        })
+        .unwrap_or_else(|| "hicolor".to_string())
+}
+
+fn cache_path() -> PathBuf {
+    let home = std::env::var("HOME").expect("HOME not set");
+    let cache_home = std::env::var("XDG_CACHE_HOME")
+        .ok()
+        .filter(|dir| !dir.is_empty())
+        .unwrap_or_else(|| format!("{home}/.cache"));
+    PathBuf::from(format!("{cache_home}/whereami/icons.toml"))
+}
+
+/// The cache is thrown away once any applications folder changed after it was written,
+/// as a newly installed app may have brought the icon a class was missing
+fn read_cache(path: &Path) -> HashMap<String, String> {
+    let modified = |path: &Path| fs::metadata(path).and_then(|m| m.modified()).ok();
+    let Some(cache_time) = modified(path) else {
+        return HashMap::new();
+    };
+    let is_stale = desktop_entries::application_dirs()
+        .iter()
+        .any(|dir| modified(dir).is_none_or(|time: SystemTime| time > cache_time));
+    if is_stale {
+        return HashMap::new();
+    }
+    fs::read_to_string(path)
+        .ok()
+        .and_then(|contents| toml::from_str(&contents).ok())
+        .unwrap_or_default()
+}
+
+/// Maps window classes to icon files, resolved through desktop entries and the icon theme.
+/// Results are cached on disk so only classes never seen before cost a lookup
+#[derive(Default)]
+pub struct Icons {
+    /// class -> icon, `None` if nothing matched. The theme's generic icon is under ""
+    paths: HashMap<String, Option<PathBuf>>,
+    /// classes being looked up by `resolve` right now
+    pending: HashSet<String>,
+}
+
+impl Icons {
+    /// What the cache knows, the rest is left to `missing` and `resolve`
+    pub fn load() -> Self {
+        let cache = read_cache(&cache_path());
+        Icons {
+            // an empty path means the class was looked up before and has no icon
+            paths: cache
+                .into_iter()
+                .map(|(class, path)| {
+                    let path = Some(PathBuf::from(path)).filter(|p| p.is_file());
+                    (class, path)
+                })
+                .collect(),
+            pending: HashSet::new(),
+        }
+    }
+
+    /// The classes that have not been looked up yet, which are then pending until `insert`
+    pub fn missing<'a>(&mut self, classes: impl Iterator<Item = &'a str>) -> Vec<String> {
+        let mut missing = std::iter::once("")
+            .chain(classes.filter(|class| !class.is_empty()))
+            .filter(|class| !self.paths.contains_key(*class) && !self.pending.contains(*class))
+            .map(str::to_string)
+            .collect::<Vec<_>>();
+        missing.sort_unstable();
+        missing.dedup();
+        self.pending.extend(missing.iter().cloned());
+        missing
+    }
+
+    /// Takes in what `resolve` found for `classes`. Any it did not get to (it panicked)
+    /// are looked up again next time
+    pub fn insert(&mut self, classes: &[String], resolved: Vec<(String, Option<PathBuf>)>) {
+        for class in classes {
+            self.pending.remove(class);
+        }
+        self.paths.extend(resolved);
+    }
+
+    /// The icon for a class, or the theme's generic one. `None` means use `GENERIC_ICON`
+    pub fn get(&self, class: &str) -> Option<&Path> {
+        self.paths
+            .get(class)
+            .and_then(Option::as_deref)
+            .or_else(|| self.paths.get("").and_then(Option::as_deref))
+    }
+}
+
+/// Looks `classes` up in the desktop entries and the icon theme, "" being the generic icon,
+/// and adds them to the cache. That reads a good few files, so it is kept off the UI thread
+pub fn resolve(classes: Vec<String>) -> Vec<(String, Option<PathBuf>)> {
+    let mut theme = THEME.lock().unwrap_or_else(PoisonError::into_inner);
+    let entries = ENTRIES.get_or_init(desktop_entries::load_all);
+    let resolved = classes
+        .into_iter()
+        .map(|class| {
+            let icon = if class.is_empty() {
+                theme.lookup("application-x-executable")
+            } else {
+                find_entry(entries, &class)
+                    .and_then(|entry| entry.icon.as_deref())
+                    .and_then(|name| theme.lookup(name))
+                    // plenty of apps name their icon after their class without a desktop entry
+                    .or_else(|| theme.lookup(&class.to_lowercase()))
+            };
+            (class, icon)
+        })
+        .collect::<Vec<_>>();
+    write_cache(&resolved);
+    resolved
+}
+
+/// Adds `resolved` to the cache on disk
+fn write_cache(resolved: &[(String, Option<PathBuf>)]) {
+    let path = cache_path();
+    let mut cache = read_cache(&path);
+    // an empty path means the class was looked up and has no icon
+    cache.extend(resolved.iter().map(|(class, icon)| {
+        let icon = icon
+            .as_ref()
+            .map(|p| p.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        (class.clone(), icon)
+    }));
+    let Ok(contents) = toml::to_string(&cache) else {
+        return;
+    };
+    // not being able to cache only makes the next start slower
+    if let Some(dir) = path.parent() {
+        let _ = fs::create_dir_all(dir);
+    }
+    let _ = fs::write(path, contents);
+}
+
+/// An entry whose id is exactly the class wins over looser matches
+fn find_entry<'a>(entries: &'a [DesktopEntry], class: &str) -> Option<&'a DesktopEntry> {
+    entries
+        .iter()
+        .find(|entry| entry.id == class)
+        .or_else(|| entries.iter().find(|entry| entry.matches_class(class)))
+}
@@ -0,0 +1,412 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use anyhow::{Result, anyhow, bail};
+use iced::keyboard::key::Named;
+use iced::keyboard::{Key, Modifiers};
+use serde::Deserialize;
+
+/// What a key can be bound to, named as in the `[keybindings]` table
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Up,
+    Down,
+    First,
+    Last,
+    PageUp,
+    PageDown,
+    /// focus the selected window / run the selected entry
+    Select,
+    Quit,
+    Close,
+    NextMode,
+    PreviousMode,
+    FocusSearch,
+    /// move the selected or marked windows to a workspace
+    Move,
+    Mark,
+    MarkAll,
+    Undo,
+    /// the action menu for the selected window
+    Actions,
+}
+
+impl FromStr for Action {
+    type Err = anyhow::Error;
+
+    fn from_str(name: &str) -> Result<Self> {
+        Ok(match name {
+            "up" => Action::Up,
+            "down" => Action::Down,
+            "first" => Action::First,
+            "last" => Action::Last,
+            "page-up" => Action::PageUp,
+            "page-down" => Action::PageDown,
+            "select" => Action::Select,
+            "quit" => Action::Quit,
+            "close" => Action::Close,
+            "next-mode" => Action::NextMode,
+            "previous-mode" => Action::PreviousMode,
+            "focus-search" => Action::FocusSearch,
+            "move" => Action::Move,
+            "mark" => Action::Mark,
+            "mark-all" => Action::MarkAll,
+            "undo" => Action::Undo,
+            "actions" => Action::Actions,
+            _ => bail!(
+                "unknown action {name:?}, expected one of up, down, first, last, page-up, \
+                 page-down, select, quit, close, \
+                 next-mode, previous-mode, focus-search, move, mark, mark-all, undo, actions \
+                 or none"
+            ),
+        })
+    }
+}
+
+/// The key of a chord, without its modifiers
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum ChordKey {
+    Named(Named),
+    /// lowercase, so `shift+g` is written with a small g like the rest
+    Character(String),
+}
+
+/// A key with the modifiers that have to be held for it, e.g. `ctrl+shift+w`
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Chord {
+    key: ChordKey,
+    modifiers: Modifiers,
+}
+
+fn named_key(name: &str) -> Option<Named> {
+    Some(match name {
+        "enter" | "return" => Named::Enter,
+        "escape" | "esc" => Named::Escape,
+        "tab" => Named::Tab,
+        "space" => Named::Space,
+        "backspace" => Named::Backspace,
+        "delete" | "del" => Named::Delete,
+        "insert" => Named::Insert,
+        "up" => Named::ArrowUp,
+        "down" => Named::ArrowDown,
+        "left" => Named::ArrowLeft,
+        "right" => Named::ArrowRight,
+        "home" => Named::Home,
+        "end" => Named::End,
+        "pageup" => Named::PageUp,
+        "pagedown" => Named::PageDown,
+        "menu" => Named::ContextMenu,
+        "f1" => Named::F1,
+        "f2" => Named::F2,
+        "f3" => Named::F3,
+        "f4" => Named::F4,
+        "f5" => Named::F5,
+        "f6" => Named::F6,
+        "f7" => Named::F7,
+        "f8" => Named::F8,
+        "f9" => Named::F9,
+        "f10" => Named::F10,
+        "f11" => Named::F11,
+        "f12" => Named::F12,
+        _ => return None,
+    })
+}
+
+impl FromStr for Chord {
+    type Err = anyhow::Error;
+
+    fn from_str(chord: &str) -> Result<Self> {
+        let lowered = chord.to_lowercase();
+        // `+` on its own (or as the last part, `ctrl++`) is the plus key
+        let (prefix, key) = match lowered.strip_suffix("++") {
+            Some(prefix) => (Some(prefix), "+"),
+            None if lowered == "+" => (None, "+"),
+            None => match lowered.rsplit_once('+') {
+                Some((prefix, key)) => (Some(prefix), key),
+                None => (None, lowered.as_str()),
+            },
+        };
+        let mut modifiers = Modifiers::empty();
+        for modifier in prefix.into_iter().flat_map(|prefix| prefix.split('+')) {
+            modifiers |= match modifier {
+                "ctrl" | "control" => Modifiers::CTRL,
+                "alt" => Modifiers::ALT,
+                "shift" => Modifiers::SHIFT,
+                "super" | "logo" | "meta" => Modifiers::LOGO,
+                _ => bail!("unknown modifier {modifier:?} in {chord:?}"),
+            };
+        }
+        let key = if let Some(named) = named_key(key) {
+            ChordKey::Named(named)
+        } else if key.chars().count() == 1 {
+            ChordKey::Character(key.to_string())
+        } else {
+            bail!("unknown key {key:?} in {chord:?}");
+        };
+        Ok(Chord::new(key, modifiers))
+    }
+}
+
+impl Chord {
+    /// Shift is left out for characters like `<` or `?`: the character already says it was
+    /// held, and which ones need it depends on the layout
+    fn new(key: ChordKey, mut modifiers: Modifiers) -> Self {
+        if let ChordKey::Character(c) = &key
+            && c.to_lowercase() == c.to_uppercase()
+        {
+            modifiers.remove(Modifiers::SHIFT);
+        }
+        Chord { key, modifiers }
+    }
+
+    fn pressed(key: &Key, modifiers: Modifiers) -> Option<Self> {
+        let key = match key.as_ref() {
+            Key::Named(named) => ChordKey::Named(named),
+            Key::Character(c) => ChordKey::Character(c.to_lowercase()),
+            Key::Unidentified => return None,
+        };
+        Some(Chord::new(key, modifiers))
+    }
+}
+
+/// The bindings every preset starts from. A plain letter or Space only works outside the
+/// search bar, so `m` and Space have a chord that works inside it too
+const DEFAULT: &[(&str, &str)] = &[
+    ("up", "up"),
+    ("down", "down"),
+    ("ctrl+p", "up"),
+    ("ctrl+n", "down"),
+    ("home", "first"),
+    ("end", "last"),
+    ("pageup", "page-up"),
+    ("pagedown", "page-down"),
+    ("enter", "select"),
+    ("escape", "quit"),
+    ("delete", "close"),
+    ("tab", "next-mode"),
+    ("shift+tab", "previous-mode"),
+    (",", "focus-search"),
+    ("m", "move"),
+    ("alt+m", "move"),
+    ("space", "mark"),
+    ("ctrl+space", "mark"),
+    ("ctrl+a", "mark-all"),
+    ("ctrl+z", "undo"),
+    ("menu", "actions"),
+    ("shift+f10", "actions"),
+];
+
+/// Only Ctrl+J/K work while typing, the rest of these need the search bar unfocused
+const VIM: &[(&str, &str)] = &[
+    ("j", "down"),
+    ("k", "up"),
+    ("ctrl+j", "down"),
+    ("ctrl+k", "up"),
+    ("o", "select"),
+    ("q", "quit"),
+    ("x", "close"),
+    ("/", "focus-search"),
+    ("u", "undo"),
+];
+
+/// No Ctrl+V for Page Down, the search bar would paste as well
+const EMACS: &[(&str, &str)] = &[
+    ("ctrl+g", "quit"),
+    ("alt+<", "first"),
+    ("alt+>", "last"),
+    ("alt+v", "page-up"),
+    ("ctrl+s", "focus-search"),
+    ("ctrl+k", "close"),
+    ("ctrl+/", "undo"),
+];
+
+/// Which key does what. Read from the `[keybindings]` table, where `preset` picks the
+/// "default", "vim" or "emacs" set and every other entry binds a chord to an action
+/// (or to "none" to free it up)
+#[derive(Debug, Clone, Deserialize)]
+#[serde(try_from = "HashMap<String, String>")]
+pub struct Keybindings {
+    bindings: HashMap<Chord, Action>,
+}
+
+impl Keybindings {
+    fn bind(&mut self, chord: &str, action: &str) -> Result<()> {
+        let chord = chord.parse::<Chord>()?;
+        if action == "none" {
+            self.bindings.remove(&chord);
+        } else {
+            self.bindings.insert(chord, action.parse()?);
+        }
+        Ok(())
+    }
+
+    fn preset(name: &str) -> Result<Self> {
+        let extra = match name {
+            "default" => &[][..],
+            "vim" => VIM,
+            "emacs" => EMACS,
+            _ => bail!("unknown keybindings preset {name:?}, expected default, vim or emacs"),
+        };
+        let mut keybindings = Keybindings {
+            bindings: HashMap::new(),
+        };
+        for (chord, action) in DEFAULT.iter().chain(extra) {
+            keybindings.bind(chord, action)?;
+        }
+        Ok(keybindings)
+    }
+
+    /// What `key` pressed with `modifiers` held does, if anything
+    pub fn action(&self, key: &Key, modifiers: Modifiers) -> Option<Action> {
+        self.bindings.get(&Chord::pressed(key, modifiers)?).copied()
+    }
+}
+
+impl Default for Keybindings {
+    fn default() -> Self {
+        Keybindings::preset("default").expect("the default keybindings are valid")
+    }
+}
+
+impl TryFrom<HashMap<String, String>> for Keybindings {
+    type Error = anyhow::Error;
+
+    fn try_from(mut table: HashMap<String, String>) -> Result<Self> {
+        let preset = table
+            .remove("preset")
+            .unwrap_or_else(|| "default".to_string());
+        let mut keybindings = Keybindings::preset(&preset)?;
+        for (chord, action) in &table {
+            keybindings
+                .bind(chord, action)
+                .map_err(|e| anyhow!("in [keybindings]: {e}"))?;
+        }
+        Ok(keybindings)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn char_key(c: &str) -> Key {
+        Key::Character(c.into())
+    }
+
+    #[test]
+    fn shifted_symbols_match_without_shift() {
+        let keybindings = Keybindings::preset("emacs").unwrap();
+        // `<` is Shift+comma on most layouts, so it arrives with Shift held
+        let alt_shift = Modifiers::ALT | Modifiers::SHIFT;
+        assert_eq!(
+            keybindings.action(&char_key("<"), alt_shift),
+            Some(Action::First)
+        );
+        assert_eq!(
+            keybindings.action(&char_key(">"), alt_shift),
+            Some(Action::Last)
+        );
+        // letters keep Shift apart
+        assert_eq!(
+            "alt+shift+<".parse::<Chord>().unwrap(),
+            "alt+<".parse().unwrap()
+        );
+        assert_ne!("shift+g".parse::<Chord>().unwrap(), "g".parse().unwrap());
+    }
+
+    #[test]
+    fn parses_chords() {
+        let plus = |modifiers| Chord {
+            key: ChordKey::Character("+".to_string()),
+            modifiers,
+        };
+        let cases = [
+            ("+", plus(Modifiers::empty())),
+            ("ctrl++", plus(Modifiers::CTRL)),
+            (
+                "Ctrl+Shift+W",
+                Chord {
+                    key: ChordKey::Character("w".to_string()),
+                    modifiers: Modifiers::CTRL | Modifiers::SHIFT,
+                },
+            ),
+            (
+                "super+esc",
+                Chord {
+                    key: ChordKey::Named(Named::Escape),
+                    modifiers: Modifiers::LOGO,
+                },
+            ),
+        ];
+        for (chord, expected) in cases {
+            assert_eq!(chord.parse::<Chord>().unwrap(), expected, "{chord}");
+        }
+    }
+
+    #[test]
+    fn rejects_unknown_modifiers_and_keys() {
+        let modifier = "hyper+x".parse::<Chord>().unwrap_err().to_string();
+        assert!(
+            modifier.contains("unknown modifier \"hyper\""),
+            "{modifier}"
+        );
+        let key = "ctrl+pgdn".parse::<Chord>().unwrap_err().to_string();
+        assert!(key.contains("unknown key \"pgdn\""), "{key}");
+        assert!("clse".parse::<Action>().is_err());
+    }
+
+    fn table(entries: &[(&str, &str)]) -> Result<Keybindings> {
+        Keybindings::try_from(
+            entries
+                .iter()
+                .map(|(chord, action)| ((*chord).to_string(), (*action).to_string()))
+                .collect::<HashMap<_, _>>(),
+        )
+    }
+
+    #[test]
+    fn presets_layer_over_the_defaults() {
+        let vim = table(&[("preset", "vim")]).unwrap();
+        assert_eq!(
+            vim.action(&char_key("j"), Modifiers::empty()),
+            Some(Action::Down)
+        );
+        // still there from DEFAULT
+        assert_eq!(
+            vim.action(&Key::Named(Named::Enter), Modifiers::empty()),
+            Some(Action::Select)
+        );
+        // presets don't mix: emacs' ctrl+k closes and it has no j
+        let emacs = table(&[("preset", "emacs")]).unwrap();
+        assert_eq!(
+            emacs.action(&char_key("k"), Modifiers::CTRL),
+            Some(Action::Close)
+        );
+        assert_eq!(emacs.action(&char_key("j"), Modifiers::empty()), None);
+        assert!(table(&[("preset", "helix")]).is_err());
+    }
+
+    #[test]
+    fn entries_override_and_unbind() {
+        let keybindings = table(&[("m", "none"), ("ctrl+j", "down"), ("enter", "close")]).unwrap();
+        assert_eq!(keybindings.action(&char_key("m"), Modifiers::empty()), None);
+        // the chord form of move is untouched
+        assert_eq!(
+            keybindings.action(&char_key("m"), Modifiers::ALT),
+            Some(Action::Move)
+        );
+        assert_eq!(
+            keybindings.action(&char_key("j"), Modifiers::CTRL),
+            Some(Action::Down)
+        );
+        assert_eq!(
+            keybindings.action(&Key::Named(Named::Enter), Modifiers::empty()),
+            Some(Action::Close)
+        );
+        let error = table(&[("x", "clse")]).unwrap_err().to_string();
+        assert!(
+            error.starts_with("in [keybindings]: unknown action"),
+            "{error}"
+        );
+    }
+}
@@ -2,15 +2,26 @@
  * All the commented out print statements are for debugging purporses
  * */
 
+mod cli;
 mod compositor;
 mod config_management;
+mod daemon;
+mod desktop_entries;
+mod icons;
+mod keybindings;
+mod output;
+mod query;
 mod search;
 mod ui;
 use std::os::unix::fs::FileExt;
+use std::sync::Arc;
 use std::{fs, process};
 
+use crate::cli::{Cli, Command};
 use crate::ui::AppState;
+use crate::ui::update::Message;
 use fd_lock::RwLock;
+use iced::Task;
 use iced_layershell::reexport::{Anchor, KeyboardInteractivity};
 use iced_layershell::settings::{LayerShellSettings, StartMode};
 use iced_layershell::{application, reexport};
@@ -54,12 +65,85 @@ fn namespace() -> String {
 }
 
 fn main() -> iced_layershell::Result {
-    let _lock = acquire_lock();
-    let config = config_management::Config::new().expect("Failed to load config");
+    let cli = Cli::parse().unwrap_or_else(|e| {
+        eprintln!("{e:#}");
+        process::exit(2);
+    });
+    let control = match cli.command {
+        // Alt+Tab pressed again while the first one is still held
+        Command::Run if cli.hold => daemon::Command::Next,
+        Command::Run => daemon::Command::Toggle,
+        Command::Control(control) => control,
+        Command::List | Command::Focus(_) | Command::Close(_) => {
+            if let Err(e) = cli.run_headless() {
+                eprintln!("{e:#}");
+                process::exit(1);
+            }
+            return Ok(());
+        }
+    };
+    // a dmenu picker is its own thing and can run next to the switcher
+    let _lock = if cli.dmenu {
+        None
+    } else {
+        // an instance is already up, so this launch only tells it what to do
+        if !cli.daemon && daemon::send(control, &cli.overrides()).is_ok() {
+            cli.warn_unforwarded();
+            return Ok(());
+        }
+        if control == daemon::Command::Hide {
+            return Ok(());
+        }
+        Some(acquire_lock())
+    };
+    let config = cli.load_config().unwrap_or_else(|e| {
+        let path = cli.config_path.as_ref().map_or_else(
+            || "~/.config/whereami/config.toml".into(),
+            |path| path.display().to_string(),
+        );
+        eprintln!("Failed to load {path}: {e}");
+        process::exit(2);
+    });
+    let compositor = if cli.dmenu {
+        Arc::new(compositor::NoCompositor)
+    } else {
+        compositor::connect(cli.backend).unwrap_or_else(|e| {
+            eprintln!("{e:#}");
+            process::exit(1);
+        })
+    };
+
+    // the daemon starts out hidden, see `AppState::hide`
+    let (size, layer, keyboard_interactivity) = if cli.daemon {
+        (
+            (1, 1),
+            reexport::Layer::Background,
+            KeyboardInteractivity::None,
+        )
+    } else {
+        (
+            (config.window.width, config.window.height),
+            reexport::Layer::Top,
+            KeyboardInteractivity::Exclusive,
+        )
+    };
 
     let theme = config.get_theme();
     application(
-        AppState::default,
+        move || {
+            let mut state = AppState::new(&cli, config.clone(), Arc::clone(&compositor));
+            // the search bar starts focused, dmenu mode has nothing else to load
+            let task = if cli.dmenu {
+                Task::done(Message::FocusSearch)
+            } else {
+                Task::batch([
+                    state.update_icons(),
+                    state.mode().load(&state),
+                    Task::done(Message::FocusSearch),
+                ])
+            };
+            (state, task)
+        },
         namespace,
         AppState::update,
         AppState::view,
@@ -67,11 +151,11 @@ fn main() -> iced_layershell::Result {
     .theme(move |_state: &AppState| theme.clone())
     .layer_settings(LayerShellSettings {
         anchor: Anchor::empty(),
-        layer: reexport::Layer::Top,
+        layer,
         exclusive_zone: 0,
         start_mode: StartMode::Active,
-        size: Some((config.window.width, config.window.height)),
-        keyboard_interactivity: KeyboardInteractivity::Exclusive,
+        size: Some(size),
+        keyboard_interactivity,
         ..Default::default()
     })
     .subscription(AppState::subscription)
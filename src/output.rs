@@ -0,0 +1,226 @@
+use std::fmt::Write;
+use std::str::FromStr;
+
+use anyhow::{Result, bail};
+
+use crate::compositor::Process;
+
+/// How `whereami list` prints the windows
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum OutputFormat {
+    /// id, workspace, class and title, tab separated
+    #[default]
+    Plain,
+    Json,
+    /// every field, tab separated, with a header line
+    Tsv,
+    /// anything with `{field}` placeholders in it, one line per window
+    Template(String),
+}
+
+/// The names usable in a template, and the TSV columns
+const FIELDS: [&str; 10] = [
+    "id",
+    "pid",
+    "title",
+    "class",
+    "workspace",
+    "workspace_name",
+    "monitor",
+    "status",
+    "focused",
+    "focus_stamp",
+];
+
+impl FromStr for OutputFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(format: &str) -> Result<Self> {
+        match format {
+            "plain" => Ok(OutputFormat::Plain),
+            "json" => Ok(OutputFormat::Json),
+            "tsv" => Ok(OutputFormat::Tsv),
+            template if template.contains('{') => {
+                // catch typos before anything is printed
+                render(template, &Process::default())?;
+                Ok(OutputFormat::Template(template.to_string()))
+            }
+            _ => bail!(
+                "unknown format {format:?}, expected plain, json, tsv or a template like \"{{id}} {{title}}\""
+            ),
+        }
+    }
+}
+
+fn field(process: &Process, name: &str) -> Option<String> {
+    Some(match name {
+        "id" => process
+            .window_id
+            .map_or_else(String::new, |id| id.to_string()),
+        "pid" => process.pid.to_string(),
+        "title" => process.title.clone(),
+        "class" => process.class.clone(),
+        "workspace" => process.workspace.to_string(),
+        "workspace_name" => process.workspace_name.clone(),
+        "monitor" => process.monitor.clone(),
+        "status" => process.status().to_string(),
+        "focused" => process.focused.to_string(),
+        "focus_stamp" => process.focus_stamp.to_string(),
+        _ => return None,
+    })
+}
+
+/// Fills in the `{field}` placeholders of `template`. `{{` and `}}` are literal braces
+fn render(template: &str, process: &Process) -> Result<String> {
+    let mut out = String::new();
+    let mut chars = template.chars().peekable();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                out.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                out.push('}');
+            }
+            '{' => {
+                let name = chars.by_ref().take_while(|&c| c != '}').collect::<String>();
+                match field(process, &name) {
+                    Some(value) => out.push_str(&value),
+                    None => bail!(
+                        "unknown field {{{name}}} in the template, expected one of {}",
+                        FIELDS.join(", ")
+                    ),
+                }
+            }
+            _ => out.push(ch),
+        }
+    }
+    Ok(out)
+}
+
+/// Tabs and newlines in a title would break the columns
+fn tsv_escape(value: &str) -> String {
+    value.replace(['\t', '\n'], " ")
+}
+
+/// The whole output for `windows`, ending with a newline
+pub fn format_windows(windows: &[Process], format: &OutputFormat) -> Result<String> {
+    let mut out = String::new();
+    match format {
+        OutputFormat::Plain => {
+            for window in windows {
+                let id = window
+                    .window_id
+                    .map_or_else(|| "-".to_string(), |id| id.to_string());
+                writeln!(
+                    out,
+                    "{id}\t{}\t{}\t{}",
+                    window.workspace,
+                    tsv_escape(&window.class),
+                    tsv_escape(&window.title)
+                )?;
+            }
+        }
+        OutputFormat::Json => {
+            out = serde_json::to_string_pretty(windows)?;
+            out.push('\n');
+        }
+        OutputFormat::Tsv => {
+            writeln!(out, "{}", FIELDS.join("\t"))?;
+            for window in windows {
+                let row = FIELDS
+                    .iter()
+                    .filter_map(|name| field(window, name))
+                    .map(|value| tsv_escape(&value))
+                    .collect::<Vec<_>>();
+                writeln!(out, "{}", row.join("\t"))?;
+            }
+        }
+        OutputFormat::Template(template) => {
+            for window in windows {
+                writeln!(out, "{}", render(template, window)?)?;
+            }
+        }
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn window() -> Process {
+        Process {
+            pid: 42,
+            title: "vim\tmain.rs\nmodified".to_string(),
+            class: "foot".to_string(),
+            window_id: Some(7),
+            workspace: 3,
+            workspace_name: "code".to_string(),
+            monitor: "DP-1".to_string(),
+            floating: true,
+            focused: true,
+            focus_stamp: 9,
+            ..Process::default()
+        }
+    }
+
+    #[test]
+    fn plain_and_tsv_escape_tabs_and_newlines() {
+        let plain = format_windows(&[window()], &OutputFormat::Plain).unwrap();
+        assert_eq!(plain, "7\t3\tfoot\tvim main.rs modified\n");
+
+        let tsv = format_windows(&[window()], &OutputFormat::Tsv).unwrap();
+        let lines = tsv.lines().collect::<Vec<_>>();
+        assert_eq!(
+            lines,
+            [
+                "id\tpid\ttitle\tclass\tworkspace\tworkspace_name\tmonitor\tstatus\tfocused\tfocus_stamp",
+                "7\t42\tvim main.rs modified\tfoot\t3\tcode\tDP-1\tFloat\ttrue\t9",
+            ]
+        );
+    }
+
+    #[test]
+    fn tsv_keeps_the_column_for_a_missing_id() {
+        let window = Process {
+            window_id: None,
+            ..window()
+        };
+        let tsv = format_windows(&[window], &OutputFormat::Tsv).unwrap();
+        let row = tsv.lines().nth(1).unwrap();
+        assert_eq!(row.split('\t').count(), FIELDS.len());
+        assert!(row.starts_with("\t42\t"), "{row}");
+    }
+
+    #[test]
+    fn json_keeps_the_title_as_is() {
+        let json = format_windows(&[window()], &OutputFormat::Json).unwrap();
+        let parsed: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed[0]["title"], "vim\tmain.rs\nmodified");
+        assert_eq!(parsed[0]["window_id"], 7);
+        assert_eq!(parsed[0]["workspace"], 3);
+        assert!(json.ends_with('\n'));
+    }
+
+    #[test]
+    fn templates_fill_in_fields_and_braces() {
+        let format = "{id} {{{class}}} {status} {workspace_name}"
+            .parse::<OutputFormat>()
+            .unwrap();
+        let out = format_windows(&[window()], &format).unwrap();
+        assert_eq!(out, "7 {foot} Float code\n");
+    }
+
+    #[test]
+    fn unknown_template_fields_are_rejected_up_front() {
+        let error = "{id} {name}"
+            .parse::<OutputFormat>()
+            .unwrap_err()
+            .to_string();
+        assert!(error.starts_with("unknown field {name}"), "{error}");
+        assert!("table".parse::<OutputFormat>().is_err());
+    }
+}
@@ -0,0 +1,330 @@
+/*
+ * The little query language the search bar understands, e.g. `ws:3 class:firefox !float rust`
+ *
+ * - `field:value` filters on one field (title, class/app, ws/workspace, mon/monitor, status)
+ * - `!term` or `-term` negates
+ * - `"quoted text"` matches literally, case included, instead of fuzzily
+ * - `a OR b` / `a | b` matches either, `( )` groups
+ * - plain words next to each other are fuzzy matched, like before
+ * */
+
+use winnow::ascii::{alpha1, multispace0, multispace1};
+use winnow::combinator::{
+    alt, cut_err, delimited, eof, fail, opt, preceded, separated, terminated,
+};
+use winnow::error::{StrContext, StrContextValue};
+use winnow::prelude::*;
+use winnow::token::{take_till, take_while};
+
+use crate::config_management::SearchField;
+use crate::search::Searchable;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Query {
+    /// a plain word
+    Text(String),
+    /// a quoted string, matched literally and with its case
+    Exact(String),
+    Field(SearchField, String),
+    Not(Box<Query>),
+    And(Vec<Query>),
+    Or(Vec<Query>),
+}
+
+/// A parsed search. Plain words at the top level are fuzzy matched and decide the ranking,
+/// everything else only decides whether a window is shown at all
+#[derive(Debug, Clone, Default)]
+pub struct ParsedQuery {
+    pub free_text: String,
+    pub filter: Option<Query>,
+}
+
+type Input<'a> = &'a str;
+
+fn field_from_name(name: &str) -> Option<SearchField> {
+    match name.to_lowercase().as_str() {
+        "title" => Some(SearchField::Title),
+        "class" | "app" => Some(SearchField::Class),
+        "ws" | "workspace" => Some(SearchField::Workspace),
+        "mon" | "monitor" => Some(SearchField::Monitor),
+        "status" => Some(SearchField::Status),
+        _ => None,
+    }
+}
+
+fn quoted(input: &mut Input<'_>) -> ModalResult<String> {
+    delimited(
+        '"',
+        take_till(0.., '"'),
+        cut_err('"').context(StrContext::Expected(StrContextValue::CharLiteral('"'))),
+    )
+    .map(String::from)
+    .parse_next(input)
+}
+
+fn word(input: &mut Input<'_>) -> ModalResult<String> {
+    take_while(1.., |c: char| {
+        !c.is_whitespace() && !matches!(c, '"' | '(' | ')' | '|')
+    })
+    // OR is the only keyword
+    .verify(|word: &str| word != "OR")
+    .map(String::from)
+    .parse_next(input)
+}
+
+fn field_filter(input: &mut Input<'_>) -> ModalResult<Query> {
+    let start = *input;
+    let name = terminated(alpha1, ':').parse_next(input)?;
+    let Some(field) = field_from_name(name) else {
+        // point at the name rather than after the colon
+        *input = start;
+        return cut_err(fail)
+            .context(StrContext::Label("field"))
+            .context(StrContext::Expected(StrContextValue::Description(
+                "title, class, ws, monitor or status",
+            )))
+            .parse_next(input);
+    };
+    let value = cut_err(alt((quoted, word)))
+        .context(StrContext::Label("value"))
+        .parse_next(input)?;
+    Ok(Query::Field(field, value))
+}
+
+fn group(input: &mut Input<'_>) -> ModalResult<Query> {
+    delimited(
+        ('(', multispace0),
+        cut_err(or_expr),
+        cut_err((multispace0, ')'))
+            .context(StrContext::Expected(StrContextValue::CharLiteral(')'))),
+    )
+    .parse_next(input)
+}
+
+fn atom(input: &mut Input<'_>) -> ModalResult<Query> {
+    alt((
+        group,
+        field_filter,
+        quoted.map(Query::Exact),
+        word.map(Query::Text),
+    ))
+    .parse_next(input)
+}
+
+fn term(input: &mut Input<'_>) -> ModalResult<Query> {
+    alt((
+        preceded(
+            alt(('!', '-')),
+            cut_err(atom).context(StrContext::Label("negated term")),
+        )
+        .map(|query| Query::Not(Box::new(query))),
+        atom,
+    ))
+    .parse_next(input)
+}
+
+fn and_expr(input: &mut Input<'_>) -> ModalResult<Query> {
+    let mut terms: Vec<Query> = separated(1.., term, multispace1).parse_next(input)?;
+    Ok(if terms.len() == 1 {
+        terms.remove(0)
+    } else {
+        Query::And(terms)
+    })
+}
+
+fn or_separator(input: &mut Input<'_>) -> ModalResult<()> {
+    (
+        multispace0,
+        alt(("|".void(), ("OR", alt((multispace1, eof))).void())),
+        multispace0,
+    )
+        .void()
+        .parse_next(input)
+}
+
+fn or_expr(input: &mut Input<'_>) -> ModalResult<Query> {
+    let mut alternatives = vec![and_expr.parse_next(input)?];
+    while opt(or_separator).parse_next(input)?.is_some() {
+        alternatives.push(
+            cut_err(and_expr)
+                .context(StrContext::Label("term after OR"))
+                .parse_next(input)?,
+        );
+    }
+    Ok(if alternatives.len() == 1 {
+        alternatives.remove(0)
+    } else {
+        Query::Or(alternatives)
+    })
+}
+
+/// Parses the search bar contents, returning a readable message when it is not valid
+pub fn parse(input: &str) -> Result<ParsedQuery, String> {
+    if input.trim().is_empty() {
+        return Ok(ParsedQuery::default());
+    }
+    let query = delimited(multispace0, or_expr, multispace0)
+        .parse(input)
+        .map_err(|e| {
+            let message = e.inner().to_string().replace('\n', ", ");
+            format!("{message} (at column {})", e.offset() + 1)
+        })?;
+
+    let mut free_text = Vec::new();
+    let filter = match query {
+        Query::Text(text) => {
+            free_text.push(text);
+            None
+        }
+        Query::And(terms) => {
+            let filters = terms
+                .into_iter()
+                .filter_map(|term| match term {
+                    Query::Text(text) => {
+                        free_text.push(text);
+                        None
+                    }
+                    other => Some(other),
+                })
+                .collect::<Vec<_>>();
+            (!filters.is_empty()).then_some(Query::And(filters))
+        }
+        other => Some(other),
+    };
+    Ok(ParsedQuery {
+        free_text: free_text.join(" "),
+        filter,
+    })
+}
+
+impl Query {
+    /// Whether an item gets past this filter. Plain and quoted words inside a filter
+    /// (e.g. `!float`, `a OR b`) look for the text in any of `fields`, only quoted ones
+    /// minding the case
+    pub fn matches<T: Searchable>(&self, item: &T, fields: &[SearchField]) -> bool {
+        let contains = |field: SearchField, text: &str| {
+            item.field_value(field)
+                .is_some_and(|value| value.to_lowercase().contains(&text.to_lowercase()))
+        };
+        match self {
+            Query::Text(text) => fields.iter().any(|&field| contains(field, text)),
+            Query::Exact(text) => fields.iter().any(|&field| {
+                item.field_value(field)
+                    .is_some_and(|value| value.contains(text.as_str()))
+            }),
+            Query::Field(SearchField::Workspace, value) => item.on_workspace(value),
+            Query::Field(field, value) => contains(*field, value),
+            Query::Not(query) => !query.matches(item, fields),
+            Query::And(queries) => queries.iter().all(|q| q.matches(item, fields)),
+            Query::Or(queries) => queries.iter().any(|q| q.matches(item, fields)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(word: &str) -> Query {
+        Query::Text(word.to_string())
+    }
+
+    #[test]
+    fn parses_filters() {
+        let cases = [
+            (
+                "class:firefox",
+                "",
+                Some(Query::Field(SearchField::Class, "firefox".to_string())),
+            ),
+            (
+                "-ws:2",
+                "",
+                Some(Query::Not(Box::new(Query::Field(
+                    SearchField::Workspace,
+                    "2".to_string(),
+                )))),
+            ),
+            ("\"a b\"", "", Some(Query::Exact("a b".to_string()))),
+            // OR binds loosest
+            (
+                "a | b c",
+                "",
+                Some(Query::Or(vec![
+                    text("a"),
+                    Query::And(vec![text("b"), text("c")]),
+                ])),
+            ),
+            (
+                "(a | b) -c",
+                "",
+                Some(Query::And(vec![
+                    Query::Or(vec![text("a"), text("b")]),
+                    Query::Not(Box::new(text("c"))),
+                ])),
+            ),
+            (
+                "rust title:\"New Tab\" OR kitty",
+                "",
+                Some(Query::Or(vec![
+                    Query::And(vec![
+                        text("rust"),
+                        Query::Field(SearchField::Title, "New Tab".to_string()),
+                    ]),
+                    text("kitty"),
+                ])),
+            ),
+            // plain words at the top level only rank
+            (
+                "rust  ws:3 cargo",
+                "rust cargo",
+                Some(Query::And(vec![Query::Field(
+                    SearchField::Workspace,
+                    "3".to_string(),
+                )])),
+            ),
+            ("firefox", "firefox", None),
+            ("   ", "", None),
+        ];
+        for (input, free_text, filter) in cases {
+            let parsed = parse(input).unwrap_or_else(|e| panic!("{input}: {e}"));
+            assert_eq!(parsed.free_text, free_text, "{input}");
+            assert_eq!(parsed.filter, filter, "{input}");
+        }
+    }
+
+    #[test]
+    fn quoted_text_keeps_its_case() {
+        let window = crate::compositor::Process {
+            title: "New Tab - Firefox".to_string(),
+            ..Default::default()
+        };
+        let fields = [SearchField::Title];
+        let cases = [
+            ("\"New Tab\"", true),
+            ("\"new tab\"", false),
+            ("-\"new tab\"", true),
+            ("title:\"new tab\"", true),
+        ];
+        for (input, matches) in cases {
+            let filter = parse(input).unwrap().filter.unwrap();
+            let found = filter.matches(&window, &fields);
+            assert_eq!(found, matches, "{input}");
+        }
+    }
+
+    #[test]
+    fn reports_where_it_went_wrong() {
+        let cases = [
+            ("\"abc", "column 5"),
+            ("ws:3 \"abc", "column 10"),
+            ("colour:red", "column 1"),
+            ("(a | b", "column 7"),
+            ("a |", "column 4"),
+        ];
+        for (input, column) in cases {
+            let error = parse(input).expect_err(input);
+            assert!(error.contains(column), "{input}: {error}");
+        }
+    }
+}
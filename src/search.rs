@@ -1,36 +1,250 @@
-use crate::AppState;
+use crate::compositor::Process;
+use crate::config_management::SearchField;
+use crate::query::{self, ParsedQuery};
+use crate::ui::{AppState, Item};
 use fuzzy_matcher::FuzzyMatcher;
 use fuzzy_matcher::skim::SkimMatcherV2;
 
-pub fn filter_search(state: &mut AppState) {
-    state.clients_to_display.clear();
+/// Anything the search bar can look through
+pub trait Searchable: Clone {
+    /// The text of `field` that the query is matched against, `None` if this kind of
+    /// item does not have that field
+    fn field_value(&self, field: SearchField) -> Option<String>;
+
+    /// Whether a `ws:` filter for `workspace` (a number or name) picks this item
+    fn on_workspace(&self, _workspace: &str) -> bool {
+        false
+    }
+}
+
+/// An item that made it through the search
+#[derive(Debug, Clone)]
+pub struct SearchMatch<T = Item> {
+    pub item: T,
+    /// the field that scored best, `None` when there is no query
+    pub field: Option<SearchField>,
+    /// positions of the characters of `field` the fuzzy matcher picked, for highlighting
+    pub indices: Vec<usize>,
+}
+
+impl SearchField {
+    pub fn label(self) -> &'static str {
+        match self {
+            SearchField::Title => "title",
+            SearchField::Class => "class",
+            SearchField::Workspace => "workspace",
+            SearchField::Monitor => "monitor",
+            SearchField::Status => "status",
+        }
+    }
+}
+
+impl Searchable for Process {
+    fn field_value(&self, field: SearchField) -> Option<String> {
+        let client = self;
+        Some(match field {
+            SearchField::Title => client.title.clone(),
+            SearchField::Class => client.class.clone(),
+            SearchField::Workspace => {
+                let number = client.workspace.to_string();
+                // Hyprland names numbered workspaces after their number
+                if client.workspace_name.is_empty() || client.workspace_name == number {
+                    number
+                } else {
+                    format!("{number} {}", client.workspace_name)
+                }
+            }
+            SearchField::Monitor => client.monitor.clone(),
+            SearchField::Status => client.status().to_string(),
+        })
+    }
+
+    fn on_workspace(&self, workspace: &str) -> bool {
+        // `ws:1` should not match workspace 12
+        self.workspace.to_string() == workspace
+            || self.workspace_name.eq_ignore_ascii_case(workspace)
+    }
+}
 
-    if state.query.is_empty() {
-        state.clients_to_display = state
-            .clients
-            .iter()
-            .map(|client| (client.clone(), client.title.clone()))
+/// Runs `query` over `items`, best match first. Also returns why the query could not be
+/// parsed, in which case it is searched for as plain text
+pub fn search<T: Searchable>(
+    items: &[T],
+    query: &str,
+    search_fields: &[SearchField],
+) -> (Vec<SearchMatch<T>>, Option<String>) {
+    // a query that does not parse is still searched for as plain text while you finish typing it
+    let (parsed, error) = match query::parse(query) {
+        Ok(parsed) => (parsed, None),
+        Err(e) => (
+            ParsedQuery {
+                free_text: query.to_string(),
+                filter: None,
+            },
+            Some(e),
+        ),
+    };
+    let candidates = items.iter().filter(|item| {
+        parsed
+            .filter
+            .as_ref()
+            .is_none_or(|filter| filter.matches(*item, search_fields))
+    });
+
+    if parsed.free_text.is_empty() {
+        let matches = candidates
+            .map(|item| SearchMatch {
+                item: item.clone(),
+                field: None,
+                indices: Vec::new(),
+            })
             .collect();
-        return;
+        return (matches, error);
     }
     let matcher = SkimMatcherV2::default();
 
-    let mut scored_clients = state
-        .clients
-        .iter()
-        .filter_map(|client| {
-            let client_title = client.title.clone();
-
-            matcher
-                .fuzzy_match(&client_title, &state.query)
-                .map(|score| (score, client.clone(), client_title.clone()))
+    let mut scored_clients = candidates
+        .filter_map(|item| {
+            // the best scoring field counts, on a tie the one listed first in the config wins
+            search_fields
+                .iter()
+                .filter_map(|&field| {
+                    matcher
+                        .fuzzy_indices(&item.field_value(field)?, &parsed.free_text)
+                        .map(|(score, indices)| (score, field, indices))
+                })
+                .fold(
+                    None,
+                    |best: Option<(i64, SearchField, Vec<usize>)>, (score, field, indices)| {
+                        match best {
+                            Some((best_score, ..)) if best_score >= score => best,
+                            _ => Some((score, field, indices)),
+                        }
+                    },
+                )
+                .map(|(score, field, indices)| {
+                    (
+                        score,
+                        SearchMatch {
+                            item: item.clone(),
+                            field: Some(field),
+                            indices,
+                        },
+                    )
+                })
         })
         .collect::<Vec<_>>();
 
-    scored_clients.sort_by(|a, b| b.0.cmp(&a.0));
+    scored_clients.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
 
-    state.clients_to_display = scored_clients
+    let results = scored_clients
         .into_iter()
-        .map(|(_, client, names)| (client, names))
+        .map(|(_, search_match)| search_match)
         .collect();
+    (results, error)
+}
+
+pub fn filter_search(state: &mut AppState) {
+    let mode = state.mode();
+    let items = mode.items(state);
+    let (matches, error) = mode.filter(state, &items);
+    state.clients_to_display = matches;
+    state.query_error = error;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn window(title: &str, class: &str, workspace: u64) -> Process {
+        Process {
+            title: title.to_string(),
+            class: class.to_string(),
+            workspace,
+            ..Default::default()
+        }
+    }
+
+    /// the title of a match, its field and indices
+    type Found<'a> = (&'a str, Option<SearchField>, &'a [usize]);
+
+    #[test]
+    fn ranks_by_the_best_field() {
+        use SearchField::{Class, Title};
+
+        let windows = [
+            window("Mozilla Firefox", "firefox", 1),
+            window("vim", "kitty", 2),
+            window("kitty", "kitty", 3),
+            window("fire", "foot", 2),
+        ];
+        // (query, fields, the matches in order)
+        let cases: [(&str, &[SearchField], &[Found]); 6] = [
+            // nothing typed keeps the order and highlights nothing
+            (
+                "",
+                &[Title, Class],
+                &[
+                    ("Mozilla Firefox", None, &[]),
+                    ("vim", None, &[]),
+                    ("kitty", None, &[]),
+                    ("fire", None, &[]),
+                ],
+            ),
+            // the class is a closer match than the title, and the indices are the class's
+            (
+                "firefox",
+                &[Title, Class],
+                &[("Mozilla Firefox", Some(Class), &[0, 1, 2, 3, 4, 5, 6])],
+            ),
+            // a tie between fields goes to the one listed first, between windows to the
+            // one listed first
+            (
+                "kitty",
+                &[Title, Class],
+                &[
+                    ("vim", Some(Class), &[0, 1, 2, 3, 4]),
+                    ("kitty", Some(Title), &[0, 1, 2, 3, 4]),
+                ],
+            ),
+            (
+                "kitty",
+                &[Class, Title],
+                &[
+                    ("vim", Some(Class), &[0, 1, 2, 3, 4]),
+                    ("kitty", Some(Class), &[0, 1, 2, 3, 4]),
+                ],
+            ),
+            // filters decide what is shown, the words what comes first
+            (
+                "ws:2 fire",
+                &[Title, Class],
+                &[("fire", Some(Title), &[0, 1, 2, 3])],
+            ),
+            // a field that is not searched is not matched
+            (
+                "firefox",
+                &[Title],
+                &[("Mozilla Firefox", Some(Title), &[8, 9, 10, 11, 12, 13, 14])],
+            ),
+        ];
+        for (query, fields, expected) in cases {
+            let (matches, error) = search(&windows, query, fields);
+            assert_eq!(error, None, "{query}");
+            let found = matches
+                .iter()
+                .map(|m| (m.item.title.as_str(), m.field, m.indices.as_slice()))
+                .collect::<Vec<_>>();
+            assert_eq!(found, expected, "{query} in {fields:?}");
+        }
+    }
+
+    #[test]
+    fn searches_a_broken_query_as_text() {
+        let windows = [window("\"quoted", "foot", 1), window("other", "foot", 1)];
+        let (matches, error) = search(&windows, "\"quo", &[SearchField::Title]);
+        assert!(error.is_some());
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].item.title, "\"quoted");
+    }
 }
@@ -0,0 +1,364 @@
+use std::collections::HashSet;
+use std::fs;
+use std::sync::Arc;
+
+use iced::Task;
+use iced::futures::future::join_all;
+
+use crate::compositor::{Capability, Compositor, Process};
+
+use super::AppState;
+use super::update::Message;
+
+/// Something the action menu can do to a window
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WindowAction {
+    ToggleFloating,
+    ToggleFullscreen,
+    TogglePin,
+    /// asks for the workspace in the search bar, like `m`
+    MoveToWorkspace,
+    MoveToMonitor(String),
+    Close,
+    /// `kill -KILL` on the pid, for windows that ignore being closed
+    Kill,
+}
+
+impl WindowAction {
+    pub fn label(&self) -> String {
+        match self {
+            WindowAction::ToggleFloating => "Toggle floating".to_string(),
+            WindowAction::ToggleFullscreen => "Toggle fullscreen".to_string(),
+            WindowAction::TogglePin => "Toggle pin".to_string(),
+            WindowAction::MoveToWorkspace => "Move to workspace...".to_string(),
+            WindowAction::MoveToMonitor(monitor) => format!("Move to monitor {monitor}"),
+            WindowAction::Close => "Close".to_string(),
+            WindowAction::Kill => "Kill process".to_string(),
+        }
+    }
+}
+
+/// What can be done to several windows at once
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BulkOp {
+    Focus,
+    Close,
+    /// to the workspace with this number or name
+    Move(String),
+    /// starts the programs closed last again, see `AppState::undo_close`
+    Reopen,
+}
+
+impl BulkOp {
+    fn verb(&self) -> &'static str {
+        match self {
+            BulkOp::Focus => "focus",
+            BulkOp::Close => "close",
+            BulkOp::Move(_) => "move",
+            BulkOp::Reopen => "reopen",
+        }
+    }
+
+    /// What to tell you once it is done, `None` if it all went to plan and there is
+    /// nothing worth saying
+    pub fn summary(&self, total: usize, failures: &[String]) -> Option<String> {
+        let windows = match (self, total) {
+            (BulkOp::Reopen, 1) => "program",
+            (BulkOp::Reopen, _) => "programs",
+            (_, 1) => "window",
+            _ => "windows",
+        };
+        if failures.is_empty() {
+            return match self {
+                BulkOp::Focus => None,
+                BulkOp::Close => Some(format!("Closed {total} {windows}")),
+                BulkOp::Move(target) => {
+                    Some(format!("Moved {total} {windows} to workspace {target}"))
+                }
+                BulkOp::Reopen => Some(format!("Reopened {total} {windows}")),
+            };
+        }
+        // a long list would push the window list off the screen
+        let mut reasons = failures.iter().take(3).cloned().collect::<Vec<_>>();
+        if failures.len() > reasons.len() {
+            reasons.push(format!("and {} more", failures.len() - reasons.len()));
+        }
+        Some(format!(
+            "Could not {} {} of {total} {windows}: {}",
+            self.verb(),
+            failures.len(),
+            reasons.join("; ")
+        ))
+    }
+}
+
+/// `value` as one word for `sh`
+fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+/// `/proc/<pid>/cmdline`, every argument ending in a NUL, as a shell command
+fn quote_cmdline(cmdline: &[u8]) -> Option<String> {
+    let args = cmdline.strip_suffix(&[0]).unwrap_or(cmdline);
+    // kernel threads and exited processes have none
+    if args.is_empty() {
+        return None;
+    }
+    let args = args
+        .split(|&byte| byte == 0)
+        .map(|arg| shell_quote(&String::from_utf8_lossy(arg)))
+        .collect::<Vec<_>>();
+    Some(args.join(" "))
+}
+
+/// A shell command that starts `pid` again the way it was started, from the folder it was
+/// in. Has to be read before the process exits
+fn relaunch_command(pid: i32) -> Option<String> {
+    let command = quote_cmdline(&fs::read(format!("/proc/{pid}/cmdline")).ok()?)?;
+    Some(match fs::read_link(format!("/proc/{pid}/cwd")) {
+        Ok(cwd) => format!("cd {} && {command}", shell_quote(&cwd.to_string_lossy())),
+        Err(_) => command,
+    })
+}
+
+/// The actions for one window, shown instead of the list while open
+#[derive(Debug, Clone)]
+pub struct ActionMenu {
+    pub window: Process,
+    pub actions: Vec<WindowAction>,
+    pub selected_idx: usize,
+}
+
+impl ActionMenu {
+    /// `monitors` are the outputs the window could go to, its own one is left out.
+    /// Only what `compositor` can do is offered
+    pub fn new(window: Process, monitors: &[String], compositor: &dyn Compositor) -> Self {
+        let mut actions = [
+            (Capability::Float, WindowAction::ToggleFloating),
+            (Capability::Fullscreen, WindowAction::ToggleFullscreen),
+            (Capability::Pin, WindowAction::TogglePin),
+            (Capability::MoveToWorkspace, WindowAction::MoveToWorkspace),
+        ]
+        .into_iter()
+        .filter(|(capability, _)| compositor.can(*capability))
+        .map(|(_, action)| action)
+        .collect::<Vec<_>>();
+        if compositor.can(Capability::MoveToMonitor) {
+            actions.extend(
+                monitors
+                    .iter()
+                    .filter(|monitor| **monitor != window.monitor)
+                    .cloned()
+                    .map(WindowAction::MoveToMonitor),
+            );
+        }
+        actions.push(WindowAction::Close);
+        // without a pid there is nothing to kill (the wlr backend never knows it)
+        if window.pid > 0 {
+            actions.push(WindowAction::Kill);
+        }
+        ActionMenu {
+            window,
+            actions,
+            selected_idx: 0,
+        }
+    }
+}
+
+impl AppState {
+    /// Does `op` to all of `windows` at the same time, and reports back which failed
+    pub fn bulk(&self, windows: Vec<Process>, op: BulkOp) -> Task<Message> {
+        let compositor = Arc::clone(&self.compositor);
+        let total = windows.len();
+        Task::perform(
+            async move {
+                let runs = windows.into_iter().map(|window| {
+                    let compositor = Arc::clone(&compositor);
+                    let op = op.clone();
+                    async move {
+                        match op {
+                            BulkOp::Focus => compositor.focus_window(window).await.map(|()| None),
+                            BulkOp::Close => {
+                                let pid = window.pid;
+                                let reopen = relaunch_command(pid).map(|command| (pid, command));
+                                compositor.close_window(window).await.map(|()| reopen)
+                            }
+                            BulkOp::Move(target) => compositor
+                                .move_to_workspace(window, target)
+                                .await
+                                .map(|()| None),
+                            // there are no windows for it yet, `undo_close` does it instead
+                            BulkOp::Reopen => Ok(None),
+                        }
+                    }
+                });
+                let mut failures = Vec::new();
+                let mut reopen = Vec::new();
+                // several windows of one process are all back once it is started again
+                let mut pids = HashSet::new();
+                for result in join_all(runs).await {
+                    match result {
+                        Ok(Some((pid, command))) if pids.insert(pid) => reopen.push(command),
+                        Ok(_) => {}
+                        Err(e) => failures.push(format!("{e:#}")),
+                    }
+                }
+                (op, failures, reopen)
+            },
+            move |(op, failures, reopen)| Message::BulkFinished {
+                op,
+                total,
+                failures,
+                reopen,
+            },
+        )
+    }
+
+    /// Closes `windows`, after asking if any of them is of a class in `confirm_close`
+    pub fn request_close(&self, windows: Vec<Process>) -> Task<Message> {
+        let prompt = match windows.as_slice() {
+            [] => return Task::none(),
+            [window] => format!("Close {}?", window.title),
+            windows => format!("Close {} windows?", windows.len()),
+        };
+        let behavior = &self.config.behavior;
+        let ask = windows.iter().any(|w| behavior.confirms_close(&w.class));
+        let close = Message::CloseWindows(windows);
+        if ask {
+            Task::done(Message::Confirm {
+                prompt,
+                then: Box::new(close),
+            })
+        } else {
+            Task::done(close)
+        }
+    }
+
+    /// Kills `window`'s process, after asking if its class is in `confirm_close` like
+    /// closing does
+    pub fn request_kill(&self, window: Process) -> Task<Message> {
+        let prompt = format!("Kill the process of {}?", window.title);
+        let ask = self.config.behavior.confirms_close(&window.class);
+        let kill = Message::KillWindow(window);
+        if ask {
+            Task::done(Message::Confirm {
+                prompt,
+                then: Box::new(kill),
+            })
+        } else {
+            Task::done(kill)
+        }
+    }
+
+    /// Starts again whatever was closed last, if it is not too late
+    pub fn undo_close(&mut self) -> Task<Message> {
+        let Some((commands, _)) = self.undo.take() else {
+            return Task::none();
+        };
+        let compositor = Arc::clone(&self.compositor);
+        let total = commands.len();
+        Task::perform(
+            async move {
+                let launches = commands
+                    .into_iter()
+                    .map(|command| compositor.launch(command));
+                join_all(launches)
+                    .await
+                    .into_iter()
+                    .filter_map(|result| result.err().map(|e| format!("{e:#}")))
+                    .collect::<Vec<_>>()
+            },
+            move |failures| Message::BulkFinished {
+                op: BulkOp::Reopen,
+                total,
+                failures,
+                reopen: Vec::new(),
+            },
+        )
+    }
+
+    /// Does `action` to `window`, then lists the windows again to show what changed.
+    /// A failure is shown under the search bar
+    pub fn run_action(&self, window: Process, action: WindowAction) -> Task<Message> {
+        let compositor = Arc::clone(&self.compositor);
+        Task::perform(
+            async move {
+                match action {
+                    WindowAction::ToggleFloating => compositor.toggle_floating(window).await,
+                    WindowAction::ToggleFullscreen => compositor.toggle_fullscreen(window).await,
+                    WindowAction::TogglePin => compositor.toggle_pin(window).await,
+                    WindowAction::MoveToMonitor(monitor) => {
+                        compositor.move_to_monitor(window, monitor).await
+                    }
+                    WindowAction::Kill => compositor.kill(window).await,
+                    // these need asking first, see `AppState::request_close` and
+                    // `AppState::start_move`
+                    WindowAction::Close | WindowAction::MoveToWorkspace => Ok(()),
+                }
+            },
+            |result| match result {
+                Ok(()) => Message::LoadClients,
+                Err(e) => Message::ActionFailed(format!("{e:#}")),
+            },
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// What `sh` makes of `command`, one line per argument
+    fn sh_args(command: &str) -> String {
+        let output = std::process::Command::new("sh")
+            .arg("-c")
+            .arg(format!("printf '%s\\n' {command}"))
+            .output()
+            .unwrap();
+        String::from_utf8(output.stdout).unwrap()
+    }
+
+    #[test]
+    fn quotes_for_the_shell() {
+        for value in [
+            "plain",
+            "two words",
+            "it's",
+            "'",
+            "",
+            "$HOME `id` \\ \"x\" *",
+        ] {
+            assert_eq!(
+                sh_args(&shell_quote(value)),
+                format!("{value}\n"),
+                "{value}"
+            );
+        }
+    }
+
+    #[test]
+    fn quotes_every_argument() {
+        let cases: [(&[u8], Option<&str>); 5] = [
+            (b"foot\0", Some("'foot'")),
+            (b"sh\0-c\0echo 'hi'\0", Some(r"'sh' '-c' 'echo '\''hi'\'''")),
+            // an empty argument stays one
+            (b"printf\0\0x\0", Some("'printf' '' 'x'")),
+            (b"no-trailing-nul", Some("'no-trailing-nul'")),
+            (b"", None),
+        ];
+        for (cmdline, command) in cases {
+            assert_eq!(quote_cmdline(cmdline).as_deref(), command);
+        }
+    }
+
+    #[test]
+    fn relaunches_this_process() {
+        let pid = i32::try_from(std::process::id()).unwrap();
+        let cwd = std::env::current_dir().unwrap();
+        let args = std::env::args().collect::<Vec<_>>();
+        let command = relaunch_command(pid).unwrap();
+        let (cd, run) = command.split_once(" && ").unwrap();
+        assert_eq!(cd, format!("cd {}", shell_quote(&cwd.to_string_lossy())));
+        assert_eq!(sh_args(run), format!("{}\n", args.join("\n")));
+    }
+}
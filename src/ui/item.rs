@@ -0,0 +1,92 @@
+use crate::compositor::{Process, Workspace};
+use crate::config_management::SearchField;
+use crate::desktop_entries::DesktopEntry;
+use crate::search::Searchable;
+
+/// Put between the titles of the windows on a workspace
+pub const TITLE_SEPARATOR: &str = " · ";
+
+/// One row of the list
+#[derive(Debug, Clone)]
+pub enum Item {
+    Window(Process),
+    Workspace(Workspace),
+    /// an application that can be launched
+    App(DesktopEntry),
+    /// a line read from stdin in dmenu mode
+    Line(String),
+}
+
+/// An app's name, with what kind of app it is after it if that says anything more
+pub(super) fn app_title(entry: &DesktopEntry) -> String {
+    match &entry.generic_name {
+        Some(generic) if *generic != entry.name => {
+            format!("{}{TITLE_SEPARATOR}{generic}", entry.name)
+        }
+        _ => entry.name.clone(),
+    }
+}
+
+impl Item {
+    /// What the icon is looked up by, see `Icons`
+    pub fn icon_key(&self) -> Option<&str> {
+        match self {
+            Item::Window(client) => Some(&client.class),
+            Item::App(entry) => Some(&entry.id),
+            Item::Workspace(_) | Item::Line(_) => None,
+        }
+    }
+}
+
+impl Searchable for Item {
+    fn field_value(&self, field: SearchField) -> Option<String> {
+        match self {
+            Item::Window(client) => client.field_value(field),
+            // a workspace is found through the windows on it
+            Item::Workspace(workspace) => match field {
+                SearchField::Title => Some(
+                    workspace
+                        .windows
+                        .iter()
+                        .map(|w| w.title.as_str())
+                        .collect::<Vec<_>>()
+                        .join(TITLE_SEPARATOR),
+                ),
+                SearchField::Class => Some(
+                    workspace
+                        .windows
+                        .iter()
+                        .map(|w| w.class.as_str())
+                        .collect::<Vec<_>>()
+                        .join(" "),
+                ),
+                SearchField::Workspace => Some(workspace.label()),
+                SearchField::Monitor => Some(workspace.monitor.clone()),
+                SearchField::Status => None,
+            },
+            Item::App(entry) => match field {
+                SearchField::Title => Some(app_title(entry)),
+                // keywords go after the id, so what the view shows still lines up
+                SearchField::Class => Some(
+                    std::iter::once(entry.id.as_str())
+                        .chain(entry.keywords.iter().map(String::as_str))
+                        .collect::<Vec<_>>()
+                        .join(" "),
+                ),
+                _ => None,
+            },
+            Item::Line(line) => (field == SearchField::Title).then(|| line.clone()),
+        }
+    }
+
+    fn on_workspace(&self, workspace: &str) -> bool {
+        match self {
+            Item::Window(client) => client.on_workspace(workspace),
+            Item::Workspace(ws) => {
+                // the same number windows go by, the id can be niri's internal one
+                ws.number.to_string() == workspace || ws.name.eq_ignore_ascii_case(workspace)
+            }
+            Item::App(_) | Item::Line(_) => false,
+        }
+    }
+}
@@ -1,61 +1,189 @@
-use crate::compositor::HyprlandCompositor;
-use crate::compositor::NiriCompositor;
+use std::collections::HashSet;
+use std::io::{self, BufRead};
 use std::sync::Arc;
 use std::sync::LazyLock;
+use std::time::Instant;
 
-use iced::widget;
+use iced::{keyboard, widget};
 
 use crate::{
-    compositor::{Compositor, Process},
-    config_management::Config,
+    cli::Cli,
+    compositor::{Compositor, Process, Workspace},
+    config_management::{Config, SortMode},
+    icons::Icons,
+    search::{SearchMatch, filter_search},
 };
 
+mod actions;
+mod item;
+pub mod modes;
 pub mod subscribe;
 pub mod update;
 pub mod view;
 
+pub use item::{Item, TITLE_SEPARATOR};
+
+use actions::ActionMenu;
+use modes::{DmenuMode, Mode};
+use update::Message;
+
 pub static TEXT_INPUT_ID: LazyLock<widget::Id> = LazyLock::new(|| widget::Id::new("search_bar"));
 
+/// Whether whereami goes away after use or stays around for the next `show`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RunMode {
+    Oneshot,
+    /// started with `--daemon`: closing only hides the surface
+    Daemon {
+        visible: bool,
+    },
+    /// `--dmenu`: prints the picked line, exits with 1 if there was none
+    Dmenu,
+}
+
 /// All the goodies for whereami. stores literally everything
 /// if you want to add something else you need to store,
 /// put it here!
 pub struct AppState {
     pub clients: Vec<Process>,
-    pub clients_to_display: Vec<(Process, String)>,
+    /// as last listed by the compositor, only kept up to date in the workspaces mode
+    pub workspaces: Vec<Workspace>,
+    pub clients_to_display: Vec<SearchMatch>,
     pub selected_idx: usize,
     pub scroll_id: widget::Id,
     pub config: Config,
     pub query: String,
     pub is_query: bool,
+    /// why the query did not parse, shown under the search bar
+    pub query_error: Option<String>,
+    /// set when the compositor's event stream dies, the list is then refreshed on a timer instead
+    pub polling: bool,
+    /// class -> icon file, empty when icons are turned off
+    pub icons: Icons,
+    pub run_mode: RunMode,
+    /// what can be listed, Tab goes through them
+    pub modes: Vec<Box<dyn Mode>>,
+    pub mode_idx: usize,
+    /// a question shown under the search bar, and what to do if Enter or Delete says yes
+    pub confirmation: Option<(String, Message)>,
+    /// open over the list after a right click on a window
+    pub menu: Option<ActionMenu>,
+    /// the windows being moved while the search bar asks where to, empty when not moving
+    pub moving: Vec<Process>,
+    /// ids of the windows picked with Space/Shift+click, which Enter, Delete and `m` then
+    /// act on all together
+    pub marked: HashSet<u64>,
+    /// `--hold`: letting go of Alt (or Ctrl or Super) focuses the selection, like Alt+Tab
+    pub hold: bool,
+    /// the keyboard modifiers held right now, for Shift+click and `hold`
+    pub modifiers: keyboard::Modifiers,
+    /// how the last action went, shown under the search bar until the next key
+    pub status: Option<String>,
+    /// commands that start the windows closed last again, until the deadline
+    pub undo: Option<(Vec<String>, Instant)>,
+    /// the top row scrolled into view, where the hints start
+    pub first_visible: usize,
+    /// see `AppState::row_height`, `None` until the list was scrolled
+    pub row_height: Option<f32>,
+    /// the mode and sort to go back to once hidden, when a launch overrode them
+    pub restore: Option<(usize, SortMode)>,
+    /// the search bar placeholder
+    pub prompt: String,
     pub compositor: Arc<dyn Compositor + Send + Sync>,
 }
 
-impl Default for AppState {
-    fn default() -> Self {
-        let config = Config::new().expect("Failed to load config");
-        let compositor = get_compositor();
-        let clients = Result::expect(compositor.get_windows(), "Failed");
-        AppState {
-            clients: clients,
+impl AppState {
+    pub fn is_visible(&self) -> bool {
+        self.run_mode != RunMode::Daemon { visible: false }
+    }
+
+    /// `config` is the one `main` loaded, with the command line already on top
+    pub fn new(cli: &Cli, config: Config, compositor: Arc<dyn Compositor + Send + Sync>) -> Self {
+        // the window stays usable without the list, it says why it is empty instead
+        let (clients, status) = match compositor.get_windows() {
+            Ok(clients) => (clients, None),
+            Err(e) => (Vec::new(), Some(format!("Could not list windows: {e:#}"))),
+        };
+        let modes = if cli.dmenu {
+            let lines = io::stdin().lock().lines().map_while(Result::ok).collect();
+            vec![Box::new(DmenuMode { lines }) as Box<dyn Mode>]
+        } else {
+            modes::all()
+        };
+        // `Cli` made sure the name exists
+        let mode_idx = cli
+            .mode
+            .as_ref()
+            .and_then(|name| modes.iter().position(|mode| mode.name() == name))
+            .unwrap_or(0);
+        let icons = if config.behavior.icons && !cli.dmenu {
+            Icons::load()
+        } else {
+            Icons::default()
+        };
+        let mut state = AppState {
+            clients,
+            workspaces: Vec::new(),
             clients_to_display: Vec::new(),
             selected_idx: 0,
             scroll_id: widget::Id::new("item_scroll"),
             config,
-            query: String::new(),
-            is_query: false,
+            query: cli.query.clone().unwrap_or_default(),
+            is_query: cli.query.as_ref().is_some_and(|query| !query.is_empty()),
+            query_error: None,
+            polling: false,
+            icons,
+            run_mode: if cli.dmenu {
+                RunMode::Dmenu
+            } else if cli.daemon {
+                RunMode::Daemon { visible: false }
+            } else {
+                RunMode::Oneshot
+            },
+            modes,
+            mode_idx,
+            confirmation: None,
+            moving: Vec::new(),
+            marked: HashSet::new(),
+            hold: cli.hold,
+            modifiers: keyboard::Modifiers::default(),
+            status,
+            undo: None,
+            menu: None,
+            first_visible: 0,
+            row_height: None,
+            restore: None,
+            prompt: cli.prompt.clone().unwrap_or_else(|| "Search".to_string()),
             compositor,
+        };
+        state.sort_clients();
+        filter_search(&mut state);
+        state
+    }
+
+    pub fn mode(&self) -> &dyn Mode {
+        self.modes[self.mode_idx].as_ref()
+    }
+
+    /// The hint shown on row `idx`, if it is one of the first few in view
+    pub fn hint(&self, idx: usize) -> Option<char> {
+        if self.menu.is_some() {
+            return None;
         }
+        let labels = self.config.behavior.hints.labels();
+        idx.checked_sub(self.first_visible)
+            .and_then(|pos| labels.get(pos))
+            .copied()
     }
-}
 
-/// Gets the current compositor used
-/// Currently only supports Hyprland and Niri
-fn get_compositor() -> Arc<dyn Compositor + Send + Sync> {
-    if std::env::var("NIRI_SOCKET").is_ok() {
-        return Arc::new(NiriCompositor::new().expect("Failed to connect to niri socket"))
-            as Arc<dyn Compositor + Send + Sync>;
-    } else if std::env::var("HYPRLAND_INSTANCE_SIGNATURE").is_ok() {
-        return Arc::new(HyprlandCompositor) as Arc<dyn Compositor + Send + Sync>;
+    /// The row whose hint is `typed`
+    pub fn hint_idx(&self, typed: &str) -> Option<usize> {
+        let mut chars = typed.chars();
+        let (Some(typed), None) = (chars.next(), chars.next()) else {
+            return None;
+        };
+        let labels = self.config.behavior.hints.labels();
+        let idx = self.first_visible + labels.iter().position(|label| *label == typed)?;
+        (self.menu.is_none() && idx < self.clients_to_display.len()).then_some(idx)
     }
-    panic!("No supported compositor found");
 }
@@ -0,0 +1,71 @@
+use std::sync::{Arc, OnceLock};
+
+use iced::Task;
+
+use crate::desktop_entries::{self, DesktopEntry};
+use crate::ui::update::Message;
+use crate::ui::{AppState, Item};
+
+use super::Mode;
+
+/// read the first time anything asks for them
+static ENTRIES: OnceLock<Vec<DesktopEntry>> = OnceLock::new();
+
+/// The desktop entries that can be launched from a menu, by name
+pub fn launchable() -> &'static [DesktopEntry] {
+    ENTRIES.get_or_init(|| {
+        let mut entries = desktop_entries::load_all();
+        entries.retain(|entry| !entry.no_display && entry.exec.is_some());
+        entries.sort_by_key(|entry| entry.name.to_lowercase());
+        entries
+    })
+}
+
+/// Starts `entry` through the compositor, in a terminal if it asks for one
+pub fn launch(state: &AppState, entry: &DesktopEntry) -> Task<Message> {
+    let Some(mut command) = entry.command_line() else {
+        return Task::none();
+    };
+    if entry.terminal {
+        let terminal = state
+            .config
+            .behavior
+            .terminal
+            .clone()
+            .or_else(|| std::env::var("TERMINAL").ok())
+            .filter(|terminal| !terminal.is_empty())
+            .unwrap_or_else(|| "xterm".to_string());
+        command = format!("{terminal} -e {command}");
+    }
+    let compositor = Arc::clone(&state.compositor);
+    Task::perform(async move { compositor.launch(command).await }, |result| {
+        if let Err(e) = result {
+            eprintln!("{e:#}");
+        }
+        Message::Quit
+    })
+}
+
+/// Launches applications from their desktop entries
+pub struct AppsMode;
+
+impl Mode for AppsMode {
+    fn name(&self) -> &'static str {
+        "apps"
+    }
+
+    fn prefix(&self) -> Option<char> {
+        Some('>')
+    }
+
+    fn items(&self, _state: &AppState) -> Vec<Item> {
+        launchable().iter().cloned().map(Item::App).collect()
+    }
+
+    fn activate(&self, state: &AppState, item: Option<&Item>) -> Task<Message> {
+        match item {
+            Some(Item::App(entry)) => launch(state, entry),
+            _ => Task::none(),
+        }
+    }
+}
@@ -0,0 +1,33 @@
+use std::process;
+
+use iced::Task;
+
+use crate::ui::update::Message;
+use crate::ui::{AppState, Item};
+
+use super::Mode;
+
+/// `--dmenu`: picks one of the lines from stdin and prints it
+pub struct DmenuMode {
+    pub lines: Vec<String>,
+}
+
+impl Mode for DmenuMode {
+    fn name(&self) -> &'static str {
+        "dmenu"
+    }
+
+    fn items(&self, _state: &AppState) -> Vec<Item> {
+        self.lines.iter().cloned().map(Item::Line).collect()
+    }
+
+    fn activate(&self, state: &AppState, item: Option<&Item>) -> Task<Message> {
+        match item {
+            Some(Item::Line(line)) => println!("{line}"),
+            // like dmenu, a query nothing matched is the answer itself
+            None if !state.query.is_empty() => println!("{}", state.query),
+            _ => return Task::none(),
+        }
+        process::exit(0);
+    }
+}
@@ -0,0 +1,59 @@
+use iced::Task;
+
+use crate::search::{self, SearchMatch};
+
+use super::update::Message;
+use super::{AppState, Item};
+
+mod apps;
+mod dmenu;
+mod windows;
+mod workspaces;
+
+pub use apps::AppsMode;
+pub use dmenu::DmenuMode;
+pub use windows::WindowsMode;
+pub use workspaces::WorkspacesMode;
+
+/// One kind of list whereami can show. Every mode is searched and navigated the same way,
+/// only what is listed and what Enter/Delete do differ
+pub trait Mode {
+    /// shown above the search bar, and what `--mode` takes
+    fn name(&self) -> &'static str;
+
+    /// typing this as the first character of the query switches to the mode
+    fn prefix(&self) -> Option<char> {
+        None
+    }
+
+    fn items(&self, state: &AppState) -> Vec<Item>;
+
+    /// Fetches what the mode lists that `AppState` does not keep up to date by itself.
+    /// Runs when the mode is switched to and whenever the windows change
+    fn load(&self, _state: &AppState) -> Task<Message> {
+        Task::none()
+    }
+
+    /// Narrows `items` down to what the query matches, best first. Also returns why the
+    /// query could not be parsed, if it couldn't
+    fn filter(&self, state: &AppState, items: &[Item]) -> (Vec<SearchMatch>, Option<String>) {
+        search::search(items, &state.query, &state.config.behavior.search_fields)
+    }
+
+    /// Enter / left click. `item` is `None` when nothing matched the query
+    fn activate(&self, state: &AppState, item: Option<&Item>) -> Task<Message>;
+
+    /// Delete / right click
+    fn secondary(&self, _state: &AppState, _item: &Item) -> Task<Message> {
+        Task::none()
+    }
+}
+
+/// The modes of the normal switcher, in the order Tab goes through them
+pub fn all() -> Vec<Box<dyn Mode>> {
+    vec![
+        Box::new(WindowsMode),
+        Box::new(WorkspacesMode),
+        Box::new(AppsMode),
+    ]
+}
@@ -0,0 +1,57 @@
+use std::sync::Arc;
+
+use iced::Task;
+
+use crate::search::{self, SearchMatch};
+use crate::ui::update::Message;
+use crate::ui::{AppState, Item};
+
+use super::{Mode, apps};
+
+/// The window switcher, what whereami started out as. When nothing open matches the
+/// query it offers to launch an app instead, so one key either focuses or starts it
+pub struct WindowsMode;
+
+impl Mode for WindowsMode {
+    fn name(&self) -> &'static str {
+        "windows"
+    }
+
+    fn items(&self, state: &AppState) -> Vec<Item> {
+        state.clients.iter().cloned().map(Item::Window).collect()
+    }
+
+    fn filter(&self, state: &AppState, items: &[Item]) -> (Vec<SearchMatch>, Option<String>) {
+        let fields = &state.config.behavior.search_fields;
+        let (matches, error) = search::search(items, &state.query, fields);
+        if !matches.is_empty() || state.query.trim().is_empty() {
+            return (matches, error);
+        }
+        let apps = apps::launchable()
+            .iter()
+            .cloned()
+            .map(Item::App)
+            .collect::<Vec<_>>();
+        (search::search(&apps, &state.query, fields).0, error)
+    }
+
+    fn activate(&self, state: &AppState, item: Option<&Item>) -> Task<Message> {
+        let client = match item {
+            Some(Item::Window(client)) => client,
+            Some(Item::App(entry)) => return apps::launch(state, entry),
+            _ => return Task::none(),
+        };
+        let compositor = Arc::clone(&state.compositor);
+        let cl = client.clone();
+        Task::perform(async move { compositor.focus_window(cl).await }, |_| {
+            Message::Quit
+        })
+    }
+
+    fn secondary(&self, state: &AppState, item: &Item) -> Task<Message> {
+        match item {
+            Item::Window(client) => state.request_close(vec![client.clone()]),
+            _ => Task::none(),
+        }
+    }
+}
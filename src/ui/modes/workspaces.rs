@@ -0,0 +1,70 @@
+use std::sync::Arc;
+
+use iced::Task;
+
+use crate::compositor::group_by_workspace;
+use crate::ui::update::Message;
+use crate::ui::{AppState, Item};
+
+use super::Mode;
+
+/// Workspaces with the windows on them
+pub struct WorkspacesMode;
+
+impl Mode for WorkspacesMode {
+    fn name(&self) -> &'static str {
+        "workspaces"
+    }
+
+    fn prefix(&self) -> Option<char> {
+        Some('@')
+    }
+
+    fn items(&self, state: &AppState) -> Vec<Item> {
+        // until the compositor has answered, make do with what the windows say
+        let workspaces = if state.workspaces.is_empty() {
+            group_by_workspace(state.clients.clone())
+        } else {
+            state.workspaces.clone()
+        };
+        workspaces.into_iter().map(Item::Workspace).collect()
+    }
+
+    fn load(&self, state: &AppState) -> Task<Message> {
+        let compositor = Arc::clone(&state.compositor);
+        Task::perform(
+            async move { compositor.get_workspaces().map_err(|e| format!("{e:#}")) },
+            Message::WorkspacesLoaded,
+        )
+    }
+
+    fn activate(&self, state: &AppState, item: Option<&Item>) -> Task<Message> {
+        let Some(Item::Workspace(workspace)) = item else {
+            return Task::none();
+        };
+        let compositor = Arc::clone(&state.compositor);
+        let ws = workspace.clone();
+        Task::perform(async move { compositor.focus_workspace(ws).await }, |_| {
+            Message::Quit
+        })
+    }
+
+    /// Closes every window on the workspace, once you have said yes
+    fn secondary(&self, _state: &AppState, item: &Item) -> Task<Message> {
+        let Item::Workspace(workspace) = item else {
+            return Task::none();
+        };
+        let prompt = match workspace.windows.len() {
+            0 => return Task::none(),
+            1 => format!("Close the window on workspace {}?", workspace.label()),
+            count => format!(
+                "Close the {count} windows on workspace {}?",
+                workspace.label()
+            ),
+        };
+        Task::done(Message::Confirm {
+            prompt,
+            then: Box::new(Message::CloseWindows(workspace.windows.clone())),
+        })
+    }
+}
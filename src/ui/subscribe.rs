@@ -1,36 +1,88 @@
-use iced::keyboard::{self, Key};
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
 
-use super::update::{Direction, Message};
+use iced::futures::StreamExt;
+use iced::keyboard;
 
-use super::AppState;
+use crate::compositor::Compositor;
+use crate::daemon;
+
+use super::update::Message;
+
+use super::{AppState, RunMode};
+
+/// `Subscription::run_with` needs something hashable to identify the stream by.
+/// There is only ever one compositor, so they all hash the same
+struct CompositorHandle(Arc<dyn Compositor + Send + Sync>);
+
+impl Hash for CompositorHandle {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        "compositor_events".hash(state);
+    }
+}
 
 impl AppState {
     pub fn subscription(&self) -> iced::Subscription<Message> {
-        /// Any key handlers will be added here
-        fn handle_keys(key: &Key, _modifiers: keyboard::Modifiers) -> Message {
-            match key.as_ref() {
-                Key::Named(iced::keyboard::key::Named::ArrowUp) => Message::Navigate(Direction::Up),
-                Key::Named(iced::keyboard::key::Named::ArrowDown) => {
-                    Message::Navigate(Direction::Down)
-                }
-                Key::Named(iced::keyboard::key::Named::Enter) => Message::ClientSelected,
-                Key::Named(iced::keyboard::key::Named::Escape) => Message::Quit,
-                Key::Named(iced::keyboard::key::Named::Delete) => Message::CloseWindow,
-                Key::Character(",") => Message::FocusSearch,
-                _ => Message::None,
-            }
-        }
-        // how often the process list is refreshed
-        iced::Subscription::batch(vec![
+        let window_events = iced::Subscription::run_with(
+            CompositorHandle(Arc::clone(&self.compositor)),
+            |handle| {
+                handle.0.window_events().map(|event| match event {
+                    Ok(event) => Message::WindowEvent(event),
+                    Err(e) => Message::EventStreamClosed(format!("{e:#}")),
+                })
+            },
+        );
+        // polling is only the fallback for when the event stream is gone
+        let poll = if self.polling {
             iced::time::every(std::time::Duration::from_millis(
                 self.config.behavior.refresh_interval,
             ))
-            .map(|_| Message::LoadClients),
-            iced::keyboard::listen().map(|event| match event {
-                iced::keyboard::Event::KeyPressed { key, modifiers, .. } => {
-                    handle_keys(&key, modifiers)
-                }
-                _ => Message::None,
+            .map(|_| Message::LoadClients)
+        } else {
+            iced::Subscription::none()
+        };
+        let undo_tick = if self.undo.is_some() {
+            iced::time::every(std::time::Duration::from_millis(500)).map(|_| Message::UndoTick)
+        } else {
+            iced::Subscription::none()
+        };
+        // a dmenu picker shows no windows and is not the one the socket is for
+        let (window_events, control) = if self.run_mode == RunMode::Dmenu {
+            (iced::Subscription::none(), iced::Subscription::none())
+        } else {
+            (
+                window_events,
+                iced::Subscription::run(daemon::listen)
+                    .map(|(command, overrides)| Message::Control(command, overrides)),
+            )
+        };
+        iced::Subscription::batch(vec![
+            window_events,
+            poll,
+            undo_tick,
+            control,
+            // the search bar's keys too, `AppState::key_pressed` sorts out which are for it
+            iced::event::listen_with(|event, status, _window| {
+                let iced::Event::Keyboard(event) = event else {
+                    return None;
+                };
+                Some(match event {
+                    keyboard::Event::KeyPressed {
+                        key,
+                        modifiers,
+                        text,
+                        ..
+                    } => Message::KeyPressed {
+                        key,
+                        modifiers,
+                        text: text.map(|text| text.to_string()),
+                        typing: status == iced::event::Status::Captured,
+                    },
+                    keyboard::Event::ModifiersChanged(modifiers) => {
+                        Message::ModifiersChanged(modifiers)
+                    }
+                    keyboard::Event::KeyReleased { key, .. } => Message::KeyReleased(key),
+                })
             }),
         ])
     }
@@ -1,134 +1,917 @@
-use std::{process, sync::Arc};
+use std::{
+    path::PathBuf,
+    process,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
+use iced::futures::channel::oneshot;
 use iced::{
-    Task,
-    widget::operation::{self, AbsoluteOffset},
+    Task, keyboard,
+    widget::{
+        operation::{self, AbsoluteOffset},
+        scrollable,
+    },
 };
+use iced_layershell::reexport::{KeyboardInteractivity, Layer};
 use iced_layershell::to_layer_message;
 
-use crate::{compositor::Process, search::filter_search};
+use crate::{
+    compositor::{Process, WindowEvent, Workspace, sort_windows},
+    daemon::{Command, Overrides},
+    icons,
+    keybindings::Action,
+    search::filter_search,
+};
 
-use super::{AppState, TEXT_INPUT_ID};
+use super::actions::{ActionMenu, BulkOp, WindowAction};
+use super::{AppState, Item, RunMode, TEXT_INPUT_ID};
 
 /// Messages for allowing the application to understand what updates it has to do
 #[to_layer_message]
 #[derive(Debug, Clone)]
 pub enum Message {
     LoadClients,
-    ClientsLoaded(Vec<Process>),
+    ClientsLoaded(Result<Vec<Process>, String>),
+    WindowEvent(WindowEvent),
+    EventStreamClosed(String),
+    WorkspacesLoaded(Result<Vec<Workspace>, String>),
+    /// class -> icon, for the `classes` `update_icons` asked about. `icons` is empty if
+    /// the lookup fell over
+    IconsResolved {
+        classes: Vec<String>,
+        icons: Vec<(String, Option<PathBuf>)>,
+    },
+    SwitchMode(usize),
+    NextMode,
+    PreviousMode,
+    /// from another launch, over the control socket, with that launch's options
+    Control(Command, Overrides),
     Quit,
     ClientSelected,
     Navigate(Direction),
     CloseWindow,
+    /// asks for a workspace to move the selected window to
+    StartMove,
+    /// opens the action menu for the selected window
+    ShowActions,
+    SelectAndShowActions(usize),
+    /// runs this entry of the action menu
+    RunAction(usize),
+    HoverAction(usize),
+    /// closes all of these at once, e.g. everything on a workspace
+    CloseWindows(Vec<Process>),
+    /// `kill -KILL` on the window's process, once confirmed
+    KillWindow(Process),
+    /// an action from the menu did not work, and why
+    ActionFailed(String),
+    BulkFinished {
+        op: BulkOp,
+        total: usize,
+        failures: Vec<String>,
+        /// how to start the closed windows again
+        reopen: Vec<String>,
+    },
+    /// Ctrl+Z: reopens what was closed last
+    Undo,
+    /// ticks while there is something to undo, to notice when it is too late
+    UndoTick,
+    /// Space: picks the selected window for a bulk action, or drops it again
+    ToggleMark,
+    /// Ctrl+A: picks every window shown, or none if they all already are
+    MarkAll,
+    ModifiersChanged(keyboard::Modifiers),
+    /// looked up in `[keybindings]` to see what it does
+    KeyPressed {
+        key: keyboard::Key,
+        modifiers: keyboard::Modifiers,
+        /// what the key types, if anything
+        text: Option<String>,
+        /// the search bar had it first
+        typing: bool,
+    },
+    /// only the modifier keys matter, for `--hold`
+    KeyReleased(keyboard::Key),
+    /// asks `prompt` first, `then` happens if the answer is yes
+    Confirm {
+        prompt: String,
+        then: Box<Message>,
+    },
     SelectAndFocus(usize),
-    SelectAndClose(usize),
     HoverWindow(usize),
+    /// the list was scrolled, which moves the hints along
+    Scrolled(scrollable::Viewport),
     UpdateInput(String),
     FocusSearch,
     None,
 }
 
+/// Whether `modifiers` hold the switcher open in `--hold` mode. Shift doesn't count, it
+/// only turns Tab around
+fn holds(modifiers: keyboard::Modifiers) -> bool {
+    modifiers.alt() || modifiers.control() || modifiers.logo()
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Direction {
     Up,
     Down,
+    First,
+    Last,
+    PageUp,
+    PageDown,
+}
+
+impl Direction {
+    /// Where the selection goes from `idx` in a list of `count`, `page` rows at a time
+    fn step(self, idx: usize, count: usize, page: usize) -> usize {
+        let last = count.saturating_sub(1);
+        match self {
+            Direction::Up if idx == 0 => last,
+            Direction::Up => idx - 1,
+            Direction::Down if idx >= last => 0,
+            Direction::Down => idx + 1,
+            Direction::First => 0,
+            Direction::Last => last,
+            Direction::PageUp => idx.saturating_sub(page),
+            Direction::PageDown => (idx + page).min(last),
+        }
+    }
+}
+
+/// What a bound key sends
+fn action_message(action: Action) -> Message {
+    match action {
+        Action::Up => Message::Navigate(Direction::Up),
+        Action::Down => Message::Navigate(Direction::Down),
+        Action::First => Message::Navigate(Direction::First),
+        Action::Last => Message::Navigate(Direction::Last),
+        Action::PageUp => Message::Navigate(Direction::PageUp),
+        Action::PageDown => Message::Navigate(Direction::PageDown),
+        Action::Select => Message::ClientSelected,
+        Action::Quit => Message::Quit,
+        Action::Close => Message::CloseWindow,
+        Action::NextMode => Message::NextMode,
+        Action::PreviousMode => Message::PreviousMode,
+        Action::FocusSearch => Message::FocusSearch,
+        Action::Move => Message::StartMove,
+        Action::Mark => Message::ToggleMark,
+        Action::MarkAll => Message::MarkAll,
+        Action::Undo => Message::Undo,
+        Action::Actions => Message::ShowActions,
+    }
 }
 
 impl AppState {
+    /// Applies a single change from the compositor to `clients`
+    fn apply_window_event(&mut self, event: WindowEvent) {
+        match event {
+            WindowEvent::Changed(process) => {
+                if let Some(existing) = self
+                    .clients
+                    .iter_mut()
+                    .find(|c| c.window_id.is_some() && c.window_id == process.window_id)
+                {
+                    *existing = process;
+                } else {
+                    self.clients.push(process);
+                }
+            }
+            WindowEvent::TitleChanged { window_id, title } => {
+                if let Some(client) = self
+                    .clients
+                    .iter_mut()
+                    .find(|c| c.window_id == Some(window_id))
+                {
+                    client.title = title;
+                }
+            }
+            WindowEvent::Closed(window_id) => {
+                self.clients.retain(|c| c.window_id != Some(window_id));
+            }
+            WindowEvent::Focused(window_id) => {
+                let newest = self.clients.iter().map(|c| c.focus_stamp).max();
+                for client in &mut self.clients {
+                    client.focused = window_id.is_some() && client.window_id == window_id;
+                    if client.focused {
+                        client.focus_stamp = newest.unwrap_or(0) + 1;
+                    }
+                }
+            }
+            WindowEvent::Reset(clients) => self.clients = clients,
+        }
+    }
+
+    /// Orders `clients` according to `behavior.sort`
+    pub fn sort_clients(&mut self) {
+        sort_windows(&mut self.clients, self.config.behavior.sort);
+    }
+
+    /// Takes a fresh list from the compositor. When it can't be reached the last list
+    /// stays up until the next load gets through
+    fn clients_loaded(&mut self, clients: Result<Vec<Process>, String>) -> Task<Message> {
+        match clients {
+            Ok(clients) => {
+                self.clients = clients;
+                self.clients_changed()
+            }
+            Err(e) => {
+                self.status = Some(format!("Could not list windows: {e}"));
+                Task::none()
+            }
+        }
+    }
+
+    /// Everything that has to happen after `clients` changed
+    fn clients_changed(&mut self) -> Task<Message> {
+        self.sort_clients();
+        // new windows may bring classes whose icons have not been looked up yet
+        let icons = self.update_icons();
+        filter_search(self);
+        // windows can disappear from under the selection at any time
+        self.selected_idx = self
+            .selected_idx
+            .min(self.clients_to_display.len().saturating_sub(1));
+        let open = self
+            .clients
+            .iter()
+            .filter_map(|c| c.window_id)
+            .collect::<Vec<_>>();
+        self.marked.retain(|id| open.contains(id));
+        Task::batch([icons, self.mode().load(self)])
+    }
+
+    /// Looks up icons for whatever the current mode lists or shows that has not been seen yet,
+    /// on a thread of its own. Rows show the generic icon until they come in
+    pub fn update_icons(&mut self) -> Task<Message> {
+        if !self.config.behavior.icons || self.run_mode == RunMode::Dmenu {
+            return Task::none();
+        }
+        let items = self.mode().items(self);
+        // the windows mode can show apps that are not among its items
+        let shown = self
+            .clients_to_display
+            .iter()
+            .map(|search_match| search_match.item.clone());
+        let items = items.into_iter().chain(shown).collect::<Vec<_>>();
+        let missing = self.icons.missing(items.iter().filter_map(Item::icon_key));
+        if missing.is_empty() {
+            return Task::none();
+        }
+        let (tx, rx) = oneshot::channel();
+        let classes = missing.clone();
+        std::thread::spawn(move || {
+            let _ = tx.send(icons::resolve(missing));
+        });
+        Task::perform(rx, move |resolved| Message::IconsResolved {
+            classes,
+            icons: resolved.unwrap_or_default(),
+        })
+    }
+
+    /// The marked windows, in list order
+    fn marked_windows(&self) -> Vec<Process> {
+        self.clients
+            .iter()
+            .filter(|c| c.window_id.is_some_and(|id| self.marked.contains(&id)))
+            .cloned()
+            .collect()
+    }
+
+    /// The row with that hint, on its own even when windows are marked
+    fn activate_hint(&mut self, idx: usize) -> Task<Message> {
+        self.selected_idx = idx;
+        self.confirmation = None;
+        let item = self
+            .clients_to_display
+            .get(idx)
+            .map(|hinted| hinted.item.clone());
+        self.mode().activate(self, item.as_ref())
+    }
+
+    fn focus_selected(&self) -> Task<Message> {
+        if !self.marked.is_empty() {
+            return self.bulk(self.marked_windows(), BulkOp::Focus);
+        }
+        let item = self
+            .clients_to_display
+            .get(self.selected_idx)
+            .map(|selected| &selected.item);
+        self.mode().activate(self, item)
+    }
+
+    fn close_selected(&self) -> Task<Message> {
+        if !self.marked.is_empty() {
+            return self.request_close(self.marked_windows());
+        }
+        let Some(selected) = self.clients_to_display.get(self.selected_idx) else {
+            return Task::none();
+        };
+        self.mode().secondary(self, &selected.item)
+    }
+
+    fn switch_mode(&mut self, idx: usize) -> Task<Message> {
+        self.mode_idx = idx;
+        self.selected_idx = 0;
+        self.first_visible = 0;
+        // rows look different in every mode
+        self.row_height = None;
+        self.confirmation = None;
+        self.moving.clear();
+        self.menu = None;
+        // only windows can be marked
+        self.marked.clear();
+        let icons = self.update_icons();
+        filter_search(self);
+        Task::batch([
+            icons,
+            self.mode().load(self),
+            operation::scroll_to::<Message>(
+                self.scroll_id.clone(),
+                AbsoluteOffset { x: 0.0, y: 0.0 },
+            ),
+        ])
+    }
+
+    fn selected_window(&self) -> Option<&Process> {
+        match self
+            .clients_to_display
+            .get(self.selected_idx)
+            .map(|selected| &selected.item)
+        {
+            Some(Item::Window(client)) => Some(client),
+            _ => None,
+        }
+    }
+
+    /// Starts asking for a workspace to move `windows` to, in the search bar
+    fn start_move(&mut self, windows: Vec<Process>) -> Task<Message> {
+        if windows.is_empty() {
+            return Task::none();
+        }
+        self.moving = windows;
+        self.confirmation = None;
+        // the list stays as it was, the query is only the workspace for now
+        self.query.clear();
+        self.is_query = false;
+        operation::focus(TEXT_INPUT_ID.clone())
+    }
+
+    /// Opens the action menu for the selected window. Anything else has no menu, so it
+    /// gets what right click always did
+    fn show_actions(&mut self) -> Task<Message> {
+        let Some(window) = self.selected_window().cloned() else {
+            return self.close_selected();
+        };
+        let mut monitors = self
+            .clients
+            .iter()
+            .map(|c| c.monitor.clone())
+            .filter(|monitor| !monitor.is_empty())
+            .collect::<Vec<_>>();
+        monitors.sort_unstable();
+        monitors.dedup();
+        self.confirmation = None;
+        self.menu = Some(ActionMenu::new(window, &monitors, self.compositor.as_ref()));
+        Task::none()
+    }
+
+    /// Runs the action picked in the menu and closes it
+    fn run_menu_action(&mut self) -> Task<Message> {
+        let Some(menu) = self.menu.take() else {
+            return Task::none();
+        };
+        match menu.actions.get(menu.selected_idx).cloned() {
+            Some(WindowAction::MoveToWorkspace) => self.start_move(vec![menu.window]),
+            Some(WindowAction::Close) => self.request_close(vec![menu.window]),
+            Some(WindowAction::Kill) => self.request_kill(menu.window),
+            Some(action) => self.run_action(menu.window, action),
+            None => Task::none(),
+        }
+    }
+
+    /// Moves the windows to the workspace typed in, or gives up if nothing was typed
+    fn finish_move(&mut self, confirmed: bool) -> Task<Message> {
+        let moving = std::mem::take(&mut self.moving);
+        let target = std::mem::take(&mut self.query).trim().to_string();
+        self.is_query = false;
+        filter_search(self);
+        if !confirmed || target.is_empty() {
+            return Task::none();
+        }
+        self.bulk(moving, BulkOp::Move(target))
+    }
+
+    /// Space and Shift+click
+    fn toggle_mark(&mut self) {
+        if let Some(id) = self.selected_window().and_then(|w| w.window_id)
+            && !self.marked.remove(&id)
+        {
+            self.marked.insert(id);
+        }
+    }
+
+    fn mark_all(&mut self) {
+        let shown = self
+            .clients_to_display
+            .iter()
+            .filter_map(|search_match| match &search_match.item {
+                Item::Window(client) => client.window_id,
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        if shown.iter().all(|id| self.marked.contains(id)) {
+            self.marked.clear();
+        } else {
+            self.marked.extend(shown);
+        }
+    }
+
+    fn bulk_finished(
+        &mut self,
+        op: &BulkOp,
+        total: usize,
+        failures: &[String],
+        reopen: Vec<String>,
+    ) -> Task<Message> {
+        self.marked.clear();
+        self.status = op.summary(total, failures);
+        let undo_for = self.config.behavior.undo_seconds;
+        if undo_for > 0 && !reopen.is_empty() {
+            self.undo = Some((reopen, Instant::now() + Duration::from_secs(undo_for)));
+            if let Some(status) = &mut self.status {
+                status.push_str(", Ctrl+Z to reopen");
+            }
+        }
+        if *op == BulkOp::Focus && failures.is_empty() {
+            return Task::done(Message::Quit);
+        }
+        Task::done(Message::LoadClients)
+    }
+
+    /// A command from another launch. Its options only count when it shows the switcher
+    fn control(&mut self, command: Command, overrides: Overrides) -> Task<Message> {
+        match (command, self.is_visible()) {
+            (Command::Show | Command::Toggle | Command::Next | Command::Previous, false) => {
+                let task = self.override_with(overrides);
+                Task::batch([task, self.show()])
+            }
+            (Command::Show, true) => self.override_with(overrides),
+            (Command::Hide | Command::Toggle, true) => Task::done(Message::Quit),
+            (Command::Next, true) => self.navigate(Direction::Down),
+            (Command::Previous, true) => self.navigate(Direction::Up),
+            _ => Task::none(),
+        }
+    }
+
+    /// Takes on another launch's `--mode`, `--query` and `--sort` until the next `hide`
+    fn override_with(&mut self, overrides: Overrides) -> Task<Message> {
+        if overrides.is_empty() {
+            return Task::none();
+        }
+        self.restore
+            .get_or_insert((self.mode_idx, self.config.behavior.sort));
+        if let Some(sort) = overrides.sort {
+            self.config.behavior.sort = sort;
+            self.sort_clients();
+            filter_search(self);
+        }
+        let mode_idx = overrides
+            .mode
+            .and_then(|name| self.modes.iter().position(|mode| mode.name() == name));
+        let task = match mode_idx {
+            Some(idx) => self.switch_mode(idx),
+            None => Task::none(),
+        };
+        match overrides.query {
+            Some(query) => Task::batch([task, self.update_input(query)]),
+            None => task,
+        }
+    }
+
+    /// Brings the daemon's surface back to its configured size, above the windows
+    fn show(&mut self) -> Task<Message> {
+        self.run_mode = RunMode::Daemon { visible: true };
+        let size = (self.config.window.width, self.config.window.height);
+        Task::batch([
+            // workspaces may have come and gone while hidden
+            self.mode().load(self),
+            Task::done(Message::SizeChange(size)),
+            Task::done(Message::LayerChange(Layer::Top)),
+            Task::done(Message::KeyboardInteractivityChange(
+                KeyboardInteractivity::Exclusive,
+            )),
+            operation::focus(TEXT_INPUT_ID.clone()),
+        ])
+    }
+
+    /// Layer shell surfaces can't be unmapped without being destroyed, so the daemon shrinks
+    /// its surface to a pixel on the background layer and lets go of the keyboard instead
+    fn hide(&mut self) -> Task<Message> {
+        self.run_mode = RunMode::Daemon { visible: false };
+        // the next show starts from a clean list
+        self.query.clear();
+        self.is_query = false;
+        self.selected_idx = 0;
+        self.confirmation = None;
+        self.moving.clear();
+        self.menu = None;
+        self.marked.clear();
+        self.status = None;
+        self.undo = None;
+        // whatever was held is let go of while hidden, without us hearing about it
+        self.modifiers = keyboard::Modifiers::default();
+        if let Some((mode_idx, sort)) = self.restore.take() {
+            self.mode_idx = mode_idx;
+            self.row_height = None;
+            self.config.behavior.sort = sort;
+            self.sort_clients();
+        }
+        let icons = self.update_icons();
+        filter_search(self);
+        Task::batch([
+            icons,
+            Task::done(Message::KeyboardInteractivityChange(
+                KeyboardInteractivity::None,
+            )),
+            Task::done(Message::LayerChange(Layer::Background)),
+            Task::done(Message::SizeChange((1, 1))),
+            operation::scroll_to::<Message>(
+                self.scroll_id.clone(),
+                AbsoluteOffset { x: 0.0, y: 0.0 },
+            ),
+        ])
+    }
+
+    /// How far down each row starts from the one before. Measured once the list has been
+    /// scrolled, until then worked out from the layout the same way `view` lays it out
+    fn row_height(&self) -> f32 {
+        if let Some(height) = self.row_height {
+            return height;
+        }
+        let font = self.config.font.size;
+        // icons are 1.5 times the text (see `icon`), text lines are 1.3 times, iced's default
+        let content = if self.config.behavior.icons {
+            font * 1.5
+        } else {
+            font * 1.3
+        };
+        content + 2.0 * self.config.layout.padding + self.config.layout.spacing
+    }
+
+    /// How many rows fit in the window, what Page Up/Down moves by
+    fn page_rows(&self) -> usize {
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        let rows = (f64::from(self.config.window.height) / f64::from(self.row_height())) as usize;
+        // the search bar and the mode names take up about two
+        rows.saturating_sub(2).max(1)
+    }
+
+    /// Keeps track of the top row in view, the same way `navigate` scrolls to one
+    fn scrolled(&mut self, viewport: scrollable::Viewport) -> Task<Message> {
+        let rows = self.clients_to_display.len();
+        // n rows with the spacing between them, the action menu has rows of its own
+        if self.menu.is_none() && rows > 0 {
+            #[allow(clippy::cast_precision_loss)]
+            let height =
+                (viewport.content_bounds().height + self.config.layout.spacing) / rows as f32;
+            self.row_height = Some(height);
+        }
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        let first_visible = (viewport.absolute_offset().y / self.row_height()).round() as usize;
+        self.first_visible = first_visible;
+        Task::none()
+    }
+
+    /// Moves the selection, Up and Down wrap around at either end
+    fn navigate(&mut self, dir: Direction) -> Task<Message> {
+        self.confirmation = None;
+        self.status = None;
+        let page = self.page_rows();
+        if let Some(menu) = &mut self.menu {
+            menu.selected_idx = dir.step(menu.selected_idx, menu.actions.len(), page);
+            return Task::none();
+        }
+        if self.clients_to_display.is_empty() {
+            return Task::none();
+        }
+        let item_height = self.row_height();
+        self.selected_idx = dir.step(self.selected_idx, self.clients_to_display.len(), page);
+
+        operation::scroll_to::<Message>(
+            self.scroll_id.clone(),
+            AbsoluteOffset {
+                x: 0.0,
+                #[allow(clippy::cast_precision_loss)]
+                y: self.selected_idx as f32 * item_height,
+            },
+        )
+    }
+
+    fn update_input(&mut self, mut content: String) -> Task<Message> {
+        // the search bar types the 1 of Alt+1 too, but that is a hint (see `key_pressed`)
+        if self.modifiers.alt()
+            && content
+                .strip_prefix(self.query.as_str())
+                .is_some_and(|typed| self.hint_idx(typed).is_some())
+        {
+            return Task::none();
+        }
+        self.status = None;
+        if !self.moving.is_empty() {
+            self.query = content;
+            return Task::none();
+        }
+        // a mode's prefix typed first switches to it, and is not part of the query
+        let prefixed = content.chars().next().and_then(|first| {
+            self.modes
+                .iter()
+                .position(|mode| mode.prefix() == Some(first))
+        });
+        let task = match prefixed {
+            Some(idx) => {
+                content.remove(0);
+                self.switch_mode(idx)
+            }
+            None => Task::none(),
+        };
+        self.query = content;
+        self.selected_idx = 0;
+        self.confirmation = None;
+        self.menu = None;
+        // when input is empty it is false, so you can revert to not searching
+        self.is_query = !self.query.is_empty();
+        filter_search(self);
+        Task::batch([task, self.update_icons()])
+    }
+
+    /// Does whatever `[keybindings]` binds the key to. `typing` is set when the search bar
+    /// had the key first, then only what doesn't edit the query is looked up. Anything
+    /// else typed outside of it goes into the query anyway
+    fn key_pressed(
+        &mut self,
+        key: &keyboard::Key,
+        modifiers: keyboard::Modifiers,
+        text: Option<&str>,
+        typing: bool,
+    ) -> Task<Message> {
+        use keyboard::key::Named;
+
+        // the chord that opened the switcher goes on moving through it
+        if self.hold && holds(modifiers) && *key == keyboard::Key::Named(Named::Tab) {
+            return self.navigate(if modifiers.shift() {
+                Direction::Up
+            } else {
+                Direction::Down
+            });
+        }
+        let plain = !holds(modifiers);
+        let hint = match key.as_ref() {
+            keyboard::Key::Character(typed) => self.hint_idx(typed),
+            _ => None,
+        };
+        // Alt+hint works anywhere, the hint on its own only outside the search bar
+        if let Some(idx) = hint
+            && modifiers == keyboard::Modifiers::ALT
+        {
+            return self.activate_hint(idx);
+        }
+        let edits_query = matches!(
+            key.as_ref(),
+            keyboard::Key::Character(_)
+                | keyboard::Key::Named(
+                    Named::Space | Named::Backspace | Named::ArrowLeft | Named::ArrowRight
+                )
+        );
+        if typing && plain && edits_query {
+            return Task::none();
+        }
+        if let Some(action) = self.config.keybindings.action(key, modifiers) {
+            return self.update(action_message(action));
+        }
+        if let Some(idx) = hint
+            && plain
+            && !typing
+        {
+            return self.activate_hint(idx);
+        }
+        match text.filter(|text| !text.is_empty() && !text.chars().any(char::is_control)) {
+            Some(text) if plain && !typing => {
+                let query = format!("{}{text}", self.query);
+                Task::batch([
+                    self.update_input(query),
+                    operation::focus(TEXT_INPUT_ID.clone()),
+                    operation::move_cursor_to_end(TEXT_INPUT_ID.clone()),
+                ])
+            }
+            _ => Task::none(),
+        }
+    }
+
+    /// In `--hold` mode, focuses the selection once the modifiers are let go of.
+    /// Some compositors only tell about it with a `ModifiersChanged`, others only with the
+    /// key being released, so both end up here and the first one wins
+    fn modifiers_released(&mut self, now: keyboard::Modifiers) -> Task<Message> {
+        let was_held = holds(self.modifiers);
+        self.modifiers = now;
+        if self.hold && was_held && !holds(now) {
+            self.update(Message::ClientSelected)
+        } else {
+            Task::none()
+        }
+    }
+
+    /// Enter and Escape while a question, the action menu or a move is waiting on them.
+    /// `None` if there is nothing waiting
+    fn answer_pending(&mut self, msg: &Message) -> Option<Task<Message>> {
+        let task = match msg {
+            Message::ClientSelected | Message::CloseWindow if self.confirmation.is_some() => self
+                .confirmation
+                .take()
+                .map_or_else(Task::none, |(_, then)| Task::done(then)),
+            // Escape only takes the question back
+            Message::Quit if self.confirmation.is_some() => {
+                self.confirmation = None;
+                Task::none()
+            }
+            Message::ClientSelected if self.menu.is_some() => self.run_menu_action(),
+            Message::Quit if self.menu.is_some() => {
+                self.menu = None;
+                Task::none()
+            }
+            Message::ClientSelected if !self.moving.is_empty() => self.finish_move(true),
+            Message::Quit if !self.moving.is_empty() => self.finish_move(false),
+            _ => return None,
+        };
+        Some(task)
+    }
+
+    /// The marking, the action menu and everything else that does something to the
+    /// windows other than focusing one
+    fn update_window_actions(&mut self, msg: Message) -> Task<Message> {
+        match msg {
+            Message::CloseWindows(windows) => self.bulk(windows, BulkOp::Close),
+            Message::KillWindow(window) => self.run_action(window, WindowAction::Kill),
+            Message::ActionFailed(reason) => {
+                self.status = Some(reason);
+                Task::done(Message::LoadClients)
+            }
+            Message::BulkFinished {
+                op,
+                total,
+                failures,
+                reopen,
+            } => self.bulk_finished(&op, total, &failures, reopen),
+            Message::Undo => self.undo_close(),
+            Message::UndoTick => {
+                if self
+                    .undo
+                    .as_ref()
+                    .is_some_and(|(_, until)| Instant::now() >= *until)
+                {
+                    self.undo = None;
+                    self.status = None;
+                }
+                Task::none()
+            }
+            Message::ToggleMark => {
+                self.toggle_mark();
+                Task::none()
+            }
+            Message::MarkAll => {
+                self.mark_all();
+                Task::none()
+            }
+            Message::ModifiersChanged(modifiers) => self.modifiers_released(modifiers),
+            Message::KeyReleased(key) => match key {
+                keyboard::Key::Named(
+                    keyboard::key::Named::Alt
+                    | keyboard::key::Named::Control
+                    | keyboard::key::Named::Super
+                    | keyboard::key::Named::Meta,
+                ) => self.modifiers_released(keyboard::Modifiers::empty()),
+                _ => Task::none(),
+            },
+            Message::StartMove => {
+                let windows = if self.marked.is_empty() {
+                    self.selected_window().cloned().into_iter().collect()
+                } else {
+                    self.marked_windows()
+                };
+                self.start_move(windows)
+            }
+            Message::ShowActions => self.show_actions(),
+            Message::SelectAndShowActions(idx) => {
+                self.selected_idx = idx;
+                self.show_actions()
+            }
+            Message::RunAction(idx) => {
+                if let Some(menu) = &mut self.menu {
+                    menu.selected_idx = idx;
+                }
+                self.run_menu_action()
+            }
+            Message::HoverAction(idx) => {
+                if let Some(menu) = &mut self.menu {
+                    menu.selected_idx = idx;
+                }
+                Task::none()
+            }
+            _ => unreachable!(),
+        }
+    }
+
     pub fn update(&mut self, msg: Message) -> Task<Message> {
+        if let Some(task) = self.answer_pending(&msg) {
+            return task;
+        }
         match msg {
             Message::LoadClients => {
                 let compositor = Arc::clone(&self.compositor);
                 Task::perform(
-                    async move { Result::expect(compositor.get_windows(), "err") },
+                    async move { compositor.get_windows().map_err(|e| format!("{e:#}")) },
                     Message::ClientsLoaded,
                 )
             }
-            Message::ClientsLoaded(clients) => {
-                self.clients = clients;
+            Message::ClientsLoaded(clients) => self.clients_loaded(clients),
+            Message::WindowEvent(event) => {
+                self.apply_window_event(event);
+                self.clients_changed()
+            }
+            Message::EventStreamClosed(e) => {
+                eprintln!("Lost the compositor event stream, falling back to polling: {e}");
+                self.polling = true;
+                Task::done(Message::LoadClients)
+            }
+            Message::WorkspacesLoaded(Ok(workspaces)) => {
+                self.workspaces = workspaces;
                 filter_search(self);
+                self.selected_idx = self
+                    .selected_idx
+                    .min(self.clients_to_display.len().saturating_sub(1));
                 Task::none()
             }
-            Message::Quit => process::exit(0),
-            Message::ClientSelected => {
-                let (selected_client, _) = &self.clients_to_display[self.selected_idx];
-                let compositor = Arc::clone(&self.compositor);
-                let cl = selected_client.clone();
-                // println!("{:?}", self.clients[client_idx].title);
-                Task::perform(async move { compositor.focus_window(cl).await }, |_| {
-                    Message::Quit
-                })
-            }
-            Message::Navigate(dir) => {
-                let item_height = self.config.layout.padding + self.config.font.size;
-                if dir.eq(&Direction::Up) {
-                    if self.selected_idx == 0 {
-                        self.selected_idx = self.clients_to_display.len() - 1;
-                    } else {
-                        self.selected_idx -= 1;
-                    }
-                } else {
-                    if self.selected_idx == self.clients_to_display.len() - 1 {
-                        self.selected_idx = 0;
-                    } else {
-                        self.selected_idx += 1;
-                    }
-                }
-                // // debug
-                // println!(
-                //     "{:?}\n{:?}",
-                //     self.clients[self.selected_idx].title,
-                //     self.clients[self.selected_idx].workspace
-                // );
-
-                operation::scroll_to::<Message>(
-                    self.scroll_id.clone(),
-                    AbsoluteOffset {
-                        x: 0.0,
-                        #[allow(clippy::cast_precision_loss)]
-                        y: self.selected_idx as f32 * item_height,
-                    },
-                )
+            Message::IconsResolved { classes, icons } => {
+                self.icons.insert(&classes, icons);
+                Task::none()
             }
-            Message::CloseWindow => {
-                let (selected_client, _) = &self.clients_to_display[self.selected_idx];
-                let cl = selected_client.clone();
-                let compositor = Arc::clone(&self.compositor);
-                // println!("{}\n{:?}", address, self.clients[self.selected_idx].title);
-                Task::perform(async move { compositor.close_window(cl).await }, |_| {
-                    Message::LoadClients
-                })
+            Message::WorkspacesLoaded(Err(e)) => {
+                eprintln!("Could not list workspaces: {e}");
+                Task::none()
             }
-            Message::SelectAndFocus(idx) => {
-                self.selected_idx = idx;
-                let compositor = Arc::clone(&self.compositor);
-                let (selected_client, _) = &self.clients_to_display[self.selected_idx];
-                let cl = selected_client.clone();
-                Task::perform(async move { compositor.focus_window(cl).await }, |_| {
-                    Message::Quit
-                })
+            Message::Confirm { prompt, then } => {
+                self.confirmation = Some((prompt, *then));
+                Task::none()
             }
-            Message::SelectAndClose(idx) => {
+            Message::Control(command, overrides) => self.control(command, overrides),
+            // dmenu exits with 1 when nothing was picked
+            Message::Quit if self.run_mode == RunMode::Dmenu => process::exit(1),
+            Message::Quit if matches!(self.run_mode, RunMode::Daemon { .. }) => self.hide(),
+            Message::Quit => process::exit(0),
+            Message::ClientSelected => self.focus_selected(),
+            Message::Navigate(dir) => self.navigate(dir),
+            Message::CloseWindow => self.close_selected(),
+            Message::KeyPressed {
+                key,
+                modifiers,
+                text,
+                typing,
+            } => self.key_pressed(&key, modifiers, text.as_deref(), typing),
+            msg @ (Message::CloseWindows(_)
+            | Message::KillWindow(_)
+            | Message::ActionFailed(_)
+            | Message::BulkFinished { .. }
+            | Message::Undo
+            | Message::UndoTick
+            | Message::ToggleMark
+            | Message::MarkAll
+            | Message::ModifiersChanged(_)
+            | Message::KeyReleased(_)
+            | Message::StartMove
+            | Message::ShowActions
+            | Message::SelectAndShowActions(_)
+            | Message::RunAction(_)
+            | Message::HoverAction(_)) => self.update_window_actions(msg),
+            Message::SelectAndFocus(idx) => {
                 self.selected_idx = idx;
-                let compositor = Arc::clone(&self.compositor);
-                let (selected_client, _) = &self.clients_to_display[self.selected_idx];
-                let cl = selected_client.clone();
-                // println!("{}\n{:?}", address, self.clients[self.selected_idx].title);
-                Task::perform(async move { compositor.close_window(cl).await }, |_| {
-                    Message::LoadClients
-                })
+                self.confirmation = None;
+                if self.modifiers.shift() {
+                    self.toggle_mark();
+                    return Task::none();
+                }
+                self.focus_selected()
             }
             Message::HoverWindow(idx) => {
                 self.selected_idx = idx;
                 Task::none()
             }
-            Message::UpdateInput(content) => {
-                self.query = content;
-                self.selected_idx = 0;
-                // when input is empty it is false, so you can revert to not searching
-                self.is_query = !self.query.is_empty();
-                Task::none()
+            Message::Scrolled(viewport) => self.scrolled(viewport),
+            Message::SwitchMode(idx) => self.switch_mode(idx),
+            Message::NextMode => self.switch_mode((self.mode_idx + 1) % self.modes.len()),
+            Message::PreviousMode => {
+                self.switch_mode((self.mode_idx + self.modes.len() - 1) % self.modes.len())
             }
+            Message::UpdateInput(content) => self.update_input(content),
             Message::FocusSearch => operation::focus(TEXT_INPUT_ID.clone()),
             Message::None => Task::none(),
             _ => unreachable!(),
@@ -1,22 +1,95 @@
-use iced::widget::{column, container, mouse_area, row, text, text_input};
-use iced::{Border, Color, Element, Length, Theme, widget};
+use std::sync::LazyLock;
 
-use crate::compositor::{FullscreenStatus, Process};
-use crate::config_management::parse_colour;
+use iced::widget::{
+    column, container, image, mouse_area, rich_text, row, span, svg, text, text_input,
+};
+use iced::{Alignment, Border, Color, Element, Length, Theme, widget};
+
+use crate::compositor::{FullscreenStatus, Process, Workspace};
+use crate::config_management::{HintStyle, SearchField, parse_colour};
+use crate::desktop_entries::DesktopEntry;
+use crate::icons::GENERIC_ICON;
+use crate::search::SearchMatch;
 
 use super::update::Message;
 
-use super::{AppState, TEXT_INPUT_ID};
+use super::actions::ActionMenu;
+use super::item::app_title;
+use super::{AppState, Item, TEXT_INPUT_ID, TITLE_SEPARATOR};
+
+static GENERIC_ICON_HANDLE: LazyLock<svg::Handle> =
+    LazyLock::new(|| svg::Handle::from_memory(GENERIC_ICON));
+
+/// `value` with the characters at `indices` drawn in `highlight`, and the rest in `base`
+/// (or the usual text colour)
+fn highlighted<'a>(
+    value: &str,
+    indices: &[usize],
+    highlight: Color,
+    base: Option<Color>,
+) -> Element<'a, Message> {
+    // neighbouring characters with the same state share one span
+    let mut spans: Vec<text::Span<'a>> = Vec::new();
+    let mut run = String::new();
+    let mut run_matched = false;
+    for (idx, ch) in value.chars().enumerate() {
+        let matched = indices.contains(&idx);
+        if matched != run_matched && !run.is_empty() {
+            let colour = if run_matched { Some(highlight) } else { base };
+            spans.push(span(std::mem::take(&mut run)).color_maybe(colour));
+        }
+        run_matched = matched;
+        run.push(ch);
+    }
+    if !run.is_empty() {
+        let colour = if run_matched { Some(highlight) } else { base };
+        spans.push(span(run).color_maybe(colour));
+    }
+    rich_text(spans).into()
+}
 
 impl AppState {
-    fn client_item<'a>(
-        &'a self,
-        idx: usize,
-        client: &Process,
-        name: &'a str,
+    /// The icon for a window class or desktop entry id
+    fn icon<'a>(&self, key: &str) -> Element<'a, Message> {
+        // a little bigger than the text so it lines up with the whole row
+        let size = self.config.font.size * 1.5;
+        match self.icons.get(key) {
+            Some(path) if path.extension().is_some_and(|ext| ext == "svg") => {
+                svg(svg::Handle::from_path(path))
+                    .width(size)
+                    .height(size)
+                    .into()
+            }
+            Some(path) => image(path).width(size).height(size).into(),
+            None => svg(GENERIC_ICON_HANDLE.clone())
+                .width(size)
+                .height(size)
+                .into(),
+        }
+    }
+
+    /// `value` of `field`, with the matched characters highlighted if `field` is what matched
+    fn match_part<'a>(
+        &self,
+        search_match: &SearchMatch,
+        field: SearchField,
+        value: &str,
+        base: Option<Color>,
     ) -> Element<'a, Message> {
-        let is_selected = idx == self.selected_idx;
-        let title = name;
+        if search_match.field == Some(field) {
+            let highlight = parse_colour(&self.config.colours.highlight);
+            highlighted(value, &search_match.indices, highlight, base)
+        } else {
+            text(value.to_string()).color_maybe(base).into()
+        }
+    }
+
+    fn window_content<'a>(
+        &'a self,
+        client: &'a Process,
+        search_match: &'a SearchMatch,
+    ) -> widget::Row<'a, Message> {
+        let title = client.title.as_str();
         let workspace_id = client.workspace;
         let status_col = match client.fullscreen {
             FullscreenStatus::Fullscreen => parse_colour(&self.config.colours.status.fullscreen),
@@ -29,22 +102,14 @@ impl AppState {
                 }
             }
         };
-        let status = match client.fullscreen {
-            FullscreenStatus::Fullscreen => "Fullscreen",
-            FullscreenStatus::Maximised => "Maximised",
-            FullscreenStatus::None => {
-                if client.floating {
-                    "Float"
-                } else {
-                    "Tiled"
-                }
-            }
-        };
+        let status = client.status();
+        let is_marked = client.window_id.is_some_and(|id| self.marked.contains(&id));
 
         // These are split into parts so they can have different colours.
         // implementation for ALL of these colours will be added sometime later.
         // Currently only supports status colours
-        let title_part = text(title);
+        let title_part = self.match_part(search_match, SearchField::Title, title, None);
+        let class_part = self.match_part(search_match, SearchField::Class, &client.class, None);
         let workspace_part = if workspace_id > 50 {
             // atleast for me, my special workspace (in a
             // scratch pad) is on workspace -98 -
@@ -57,15 +122,141 @@ impl AppState {
         } else {
             text(format!("@Workspace: {workspace_id}"))
         };
-        let status_part = text(format!("[{status}]")).style(move |_| text::Style {
-            color: Some(status_col),
-        });
+        let status_part = row![
+            text("[").color(status_col),
+            self.match_part(search_match, SearchField::Status, status, Some(status_col)),
+            text("]").color(status_col),
+        ];
 
         // brings all together
-        let item_content: widget::Row<'_, _, _, _> =
-            row!(title_part, workspace_part, status_part).spacing(self.config.layout.spacing);
+        let mut item_content: widget::Row<'_, _, _, _> = row![]
+            .spacing(self.config.layout.spacing)
+            .align_y(Alignment::Center);
+        if is_marked {
+            let highlight = parse_colour(&self.config.colours.highlight);
+            item_content = item_content.push(text("●").color(highlight));
+        }
+        if self.config.behavior.icons {
+            item_content = item_content.push(self.icon(&client.class));
+        }
+        let item_content = item_content
+            .push(title_part)
+            .push(class_part)
+            .push(workspace_part)
+            .push(status_part);
+        // the title is right there, anything else is worth pointing out
+        match search_match.field {
+            Some(field) if field != SearchField::Title => {
+                item_content.push(text(format!("(matched {})", field.label())))
+            }
+            _ => item_content,
+        }
+    }
+
+    fn workspace_content<'a>(
+        &'a self,
+        workspace: &'a Workspace,
+        search_match: &'a SearchMatch,
+    ) -> widget::Row<'a, Message> {
+        let windows = match workspace.windows.len() {
+            1 => "1 window".to_string(),
+            count => format!("{count} windows"),
+        };
+        let titles = workspace
+            .windows
+            .iter()
+            .map(|w| w.title.as_str())
+            .collect::<Vec<_>>()
+            .join(TITLE_SEPARATOR);
+        let mut item_content = row![
+            self.match_part(
+                search_match,
+                SearchField::Workspace,
+                &workspace.label(),
+                None
+            ),
+            self.match_part(search_match, SearchField::Monitor, &workspace.monitor, None),
+            text(windows),
+            self.match_part(search_match, SearchField::Title, &titles, None),
+        ]
+        .spacing(self.config.layout.spacing)
+        .align_y(Alignment::Center);
+        if search_match.field == Some(SearchField::Class) {
+            item_content = item_content.push(text("(matched class)"));
+        }
+        item_content
+    }
+
+    fn app_content<'a>(
+        &'a self,
+        entry: &'a DesktopEntry,
+        search_match: &'a SearchMatch,
+    ) -> widget::Row<'a, Message> {
+        let mut item_content: widget::Row<'_, _, _, _> = row![]
+            .spacing(self.config.layout.spacing)
+            .align_y(Alignment::Center);
+        if self.config.behavior.icons {
+            item_content = item_content.push(self.icon(&entry.id));
+        }
+        let item_content = item_content
+            .push(self.match_part(search_match, SearchField::Title, &app_title(entry), None))
+            .push(self.match_part(search_match, SearchField::Class, &entry.id, None));
+        // a keyword match has nothing to highlight in the id
+        let id_len = entry.id.chars().count();
+        if search_match.field == Some(SearchField::Class)
+            && search_match.indices.iter().any(|&idx| idx >= id_len)
+        {
+            item_content.push(text("(matched keyword)"))
+        } else {
+            item_content
+        }
+    }
 
-        let styled = if is_selected {
+    /// The modes to click through, with the current one picked out like the selected row
+    fn mode_bar(&self) -> Element<'_, Message> {
+        let tabs = self.modes.iter().enumerate().map(|(idx, mode)| {
+            let is_current = idx == self.mode_idx;
+            let label = container(text(mode.name()))
+                .padding([self.config.layout.padding / 2.0, self.config.layout.padding])
+                .style(move |theme: &Theme| container::Style {
+                    background: is_current.then(|| theme.palette().primary.into()),
+                    text_color: Some(if is_current {
+                        theme.palette().background
+                    } else {
+                        theme.palette().text
+                    }),
+                    border: Border {
+                        radius: self.config.layout.border_radius.into(),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                });
+            mouse_area(label)
+                .on_press(Message::SwitchMode(idx))
+                .interaction(iced::mouse::Interaction::Pointer)
+                .into()
+        });
+        row(tabs).spacing(self.config.layout.spacing).into()
+    }
+
+    /// A line under the search bar saying what Enter and Escape do right now
+    fn notice<'a>(&self, message: String) -> Element<'a, Message> {
+        let highlight = parse_colour(&self.config.colours.highlight);
+        text(message)
+            .size(self.config.font.size * 0.8)
+            .style(move |_| text::Style {
+                color: Some(highlight),
+            })
+            .into()
+    }
+
+    /// A row of the list, picked out if it is the selected one
+    fn styled_row<'a>(
+        &'a self,
+        item_content: impl Into<Element<'a, Message>>,
+        is_selected: bool,
+    ) -> widget::Container<'a, Message> {
+        if is_selected {
             container(item_content)
                 .width(Length::Fill)
                 .style(|theme: &Theme| container::Style {
@@ -103,25 +294,74 @@ impl AppState {
                 })
                 .padding([self.config.layout.padding, self.config.layout.margin])
         }
-        .width(Length::Shrink);
+        .width(Length::Shrink)
+    }
 
-        mouse_area(styled)
+    fn list_item<'a>(&'a self, idx: usize, search_match: &'a SearchMatch) -> Element<'a, Message> {
+        let item_content = match &search_match.item {
+            Item::Window(client) => self.window_content(client, search_match),
+            Item::Workspace(workspace) => self.workspace_content(workspace, search_match),
+            Item::App(entry) => self.app_content(entry, search_match),
+            Item::Line(line) => row![self.match_part(search_match, SearchField::Title, line, None)],
+        };
+        let item_content = if self.config.behavior.hints == HintStyle::Off {
+            item_content
+        } else {
+            // rows without one keep the space, so the list stays lined up
+            let hint = self.hint(idx).map(String::from).unwrap_or_default();
+            let highlight = parse_colour(&self.config.colours.highlight);
+            row![
+                text(hint)
+                    .color(highlight)
+                    .width(Length::Fixed(self.config.font.size))
+            ]
+            .push(item_content)
+            .spacing(self.config.layout.spacing)
+            .align_y(Alignment::Center)
+        };
+        mouse_area(self.styled_row(item_content, idx == self.selected_idx))
             .on_press(Message::SelectAndFocus(idx))
-            .on_right_press(Message::SelectAndClose(idx))
+            .on_right_press(Message::SelectAndShowActions(idx))
             .on_enter(Message::HoverWindow(idx))
             .interaction(iced::mouse::Interaction::Pointer)
             .into()
     }
 
+    /// What can be done to the window the menu is open for, in place of the list
+    fn action_menu<'a>(&'a self, menu: &'a ActionMenu) -> Element<'a, Message> {
+        let actions = menu.actions.iter().enumerate().map(|(idx, action)| {
+            mouse_area(self.styled_row(text(action.label()), idx == menu.selected_idx))
+                .on_press(Message::RunAction(idx))
+                .on_enter(Message::HoverAction(idx))
+                .interaction(iced::mouse::Interaction::Pointer)
+                .into()
+        });
+        column![text(format!(
+            "{} ({})",
+            menu.window.title, menu.window.class
+        ))]
+        .extend(actions)
+        .spacing(self.config.layout.spacing)
+        .into()
+    }
+
     pub fn view(&self) -> Element<'_, Message> {
+        if !self.is_visible() {
+            return column![].into();
+        }
         let items: Vec<_> = self
             .clients_to_display
             .iter()
             .enumerate()
-            .map(|(idx, (client, name))| self.client_item(idx, client, name))
+            .map(|(idx, search_match)| self.list_item(idx, search_match))
             .collect();
+        let placeholder = if self.moving.is_empty() {
+            &self.prompt
+        } else {
+            "Workspace"
+        };
         let search_bar_widget = Element::from(
-            text_input("Search", &self.query)
+            text_input(placeholder, &self.query)
                 .id(TEXT_INPUT_ID.clone())
                 .style(|_, _| text_input::Style {
                     background: parse_colour(&self.config.colours.search_background).into(),
@@ -136,20 +376,59 @@ impl AppState {
                     value: parse_colour(&self.config.colours.selected_text),
                 })
                 .on_input(Message::UpdateInput)
-                .on_submit(Message::ClientSelected)
                 .padding(self.config.layout.padding)
                 .size(self.config.font.size),
         );
 
-        let scrollable_list: Element<'_, Message> = widget::Scrollable::new(
-            column(items).spacing(self.config.layout.spacing), // Put all client elements in a column
-        )
-        .width(Length::Fill)
-        .height(Length::Fill) // The scrollable part should fill available vertical space
-        .id(self.scroll_id.clone())
-        .into();
+        let list = match &self.menu {
+            Some(menu) => self.action_menu(menu),
+            // Put all client elements in a column
+            None => column(items).spacing(self.config.layout.spacing).into(),
+        };
+        let scrollable_list: Element<'_, Message> = widget::Scrollable::new(list)
+            .width(Length::Fill)
+            .height(Length::Fill) // The scrollable part should fill available vertical space
+            .id(self.scroll_id.clone())
+            .on_scroll(Message::Scrolled)
+            .into();
 
-        let root_layout = column![search_bar_widget, scrollable_list,]
+        let mut root_layout = column![].spacing(self.config.layout.spacing);
+        if self.modes.len() > 1 {
+            root_layout = root_layout.push(self.mode_bar());
+        }
+        root_layout = root_layout.push(search_bar_widget);
+        if let Some(error) = &self.query_error {
+            let error_col = parse_colour(&self.config.colours.status.fullscreen);
+            root_layout = root_layout.push(text(error).size(self.config.font.size * 0.8).style(
+                move |_| text::Style {
+                    color: Some(error_col),
+                },
+            ));
+        }
+        if let Some((prompt, _)) = &self.confirmation {
+            root_layout = root_layout
+                .push(self.notice(format!("{prompt} Enter to confirm, Escape to cancel")));
+        }
+        let moving = match self.moving.as_slice() {
+            [] => None,
+            [window] => Some(window.title.clone()),
+            windows => Some(format!("{} windows", windows.len())),
+        };
+        if let Some(moving) = moving {
+            root_layout = root_layout.push(self.notice(format!(
+                "Moving {moving}: type a workspace number or name, Enter to move, Escape to cancel"
+            )));
+        } else if !self.marked.is_empty() {
+            root_layout = root_layout.push(self.notice(format!(
+                "{} marked: Enter focuses, Delete closes and m moves them all",
+                self.marked.len()
+            )));
+        }
+        if let Some(status) = &self.status {
+            root_layout = root_layout.push(self.notice(status.clone()));
+        }
+        let root_layout = root_layout
+            .push(scrollable_list)
             .spacing(self.config.layout.spacing)
             .width(Length::Fill)
             .height(Length::Fill);